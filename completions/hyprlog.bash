@@ -0,0 +1,71 @@
+# bash completion for hyprlog
+
+_hyprlog() {
+    local cur prev
+    cur="${COMP_WORDS[COMP_CWORD]}"
+    prev="${COMP_WORDS[COMP_CWORD-1]}"
+    COMPREPLY=()
+
+    case "$prev" in
+        --preset)
+            COMPREPLY=($(compgen -W "$(hyprlog --list-presets 2>/dev/null)" -- "$cur"))
+            return
+            ;;
+        --by | -b)
+            COMPREPLY=($(compgen -W "class category" -- "$cur"))
+            return
+            ;;
+        --sort)
+            COMPREPLY=($(compgen -W "duration name" -- "$cur"))
+            return
+            ;;
+        --color)
+            COMPREPLY=($(compgen -W "auto always never" -- "$cur"))
+            return
+            ;;
+        --format)
+            COMPREPLY=($(compgen -W "terminal json csv tsv markdown" -- "$cur"))
+            return
+            ;;
+        --html | --svg)
+            COMPREPLY=($(compgen -f -- "$cur"))
+            return
+            ;;
+        --glyphs)
+            COMPREPLY=($(compgen -W "fancy blocks ascii" -- "$cur"))
+            return
+            ;;
+        --days | -d | --class | -c | --category | --exclude | -e | --top | -t | --min-percent | --width | -w | --buckets | --svg-width | --svg-height)
+            return
+            ;;
+    esac
+
+    if [[ "$cur" == @* ]]; then
+        COMPREPLY=($(compgen -P @ -W "$(hyprlog --list-presets 2>/dev/null)" -- "${cur#@}"))
+        return
+    fi
+
+    if [[ $COMP_CWORD -eq 1 && "$cur" != -* ]]; then
+        COMPREPLY=($(compgen -W "report timeline bar watch config" -- "$cur"))
+        return
+    fi
+    if [[ "${COMP_WORDS[1]}" == config ]]; then
+        if [[ $COMP_CWORD -eq 2 ]]; then
+            COMPREPLY=($(compgen -W "check" -- "$cur"))
+        fi
+        return
+    fi
+    if [[ "${COMP_WORDS[1]}" == report ]]; then
+        COMPREPLY=($(compgen -W "--html" -- "$cur"))
+    elif [[ "${COMP_WORDS[1]}" == timeline ]]; then
+        COMPREPLY=($(compgen -W "--svg --svg-width --svg-height --no-labels" -- "$cur"))
+    elif [[ "${COMP_WORDS[1]}" == bar ]]; then
+        COMPREPLY=($(compgen -W "--follow" -- "$cur"))
+    fi
+
+    COMPREPLY+=($(compgen -W "--help --preset --full --multi --stacked --patterns --no-full --no-multi --no-stacked --no-patterns --inhibited --strict
+        --days --class --by --category --exclude --top --min-percent --all --sort --glyphs --color --width --format --buckets --no-config
+        --idle --resume" -- "$cur"))
+}
+
+complete -F _hyprlog hyprlog
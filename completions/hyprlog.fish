@@ -0,0 +1,49 @@
+# fish completion for hyprlog
+
+function __hyprlog_presets
+    hyprlog --list-presets 2>/dev/null
+end
+
+complete -c hyprlog -f
+complete -c hyprlog -a "(__hyprlog_presets | string replace -r '^' @)" -d preset
+complete -c hyprlog -l preset -x -a "(__hyprlog_presets)" -d "Apply a preset from hyprlog.conf"
+complete -c hyprlog -s h -l help -d "Show usage"
+complete -c hyprlog -s f -l full -d "Show titles"
+complete -c hyprlog -s m -l multi -d "One timeline per row"
+complete -c hyprlog -s s -l stacked -d "One timeline per day"
+complete -c hyprlog -s p -l patterns -d "Letters instead of blocks"
+complete -c hyprlog -l no-full -d "Hide titles"
+complete -c hyprlog -l no-multi -d "A single timeline"
+complete -c hyprlog -l no-stacked -d "One timeline for all days"
+complete -c hyprlog -l no-patterns -d "Blocks instead of letters"
+complete -c hyprlog -l inhibited -d "Show discounted idle time"
+complete -c hyprlog -l strict -d "Don't guess missing events"
+complete -c hyprlog -s d -l days -x -d "Number of days"
+complete -c hyprlog -s c -l class -x -d "Report titles of a class"
+complete -c hyprlog -s b -l by -x -a "class category" -d "Group rows by"
+complete -c hyprlog -l category -x -d "Report classes of a category"
+complete -c hyprlog -s e -l exclude -x -d "Ignore a class pattern"
+complete -c hyprlog -s t -l top -x -d "Keep the N longest rows"
+complete -c hyprlog -l min-percent -x -d "Keep rows above a percentage"
+complete -c hyprlog -s a -l all -d "Show every row"
+complete -c hyprlog -l sort -x -a "duration name" -d "Row order"
+complete -c hyprlog -l glyphs -x -a "fancy blocks ascii" -d "Timeline characters"
+complete -c hyprlog -l color -x -a "auto always never" -d "When to use colours"
+complete -c hyprlog -s w -l width -x -d "Columns to draw in"
+complete -c hyprlog -l format -x -a "terminal json csv tsv markdown" -d "Output format"
+complete -c hyprlog -l buckets -x -d "Timeline sections in --format output"
+complete -c hyprlog -n __fish_use_subcommand -a report -d "Write the report to a file"
+complete -c hyprlog -n __fish_use_subcommand -a timeline -d "Write the timeline to a file"
+complete -c hyprlog -n __fish_use_subcommand -a bar -d "Status for Waybar"
+complete -c hyprlog -n __fish_use_subcommand -a watch -d "Keep the report open and up to date"
+complete -c hyprlog -n __fish_use_subcommand -a config -d "Inspect hyprlog.conf"
+complete -c hyprlog -n "__fish_seen_subcommand_from config; and not __fish_seen_subcommand_from check" -a check -d "Print the effective config and its problems"
+complete -c hyprlog -n "__fish_seen_subcommand_from report" -l html -r -F -d "HTML file to write"
+complete -c hyprlog -n "__fish_seen_subcommand_from timeline" -l svg -r -F -d "SVG file to write"
+complete -c hyprlog -n "__fish_seen_subcommand_from timeline" -l svg-width -x -d "Image width"
+complete -c hyprlog -n "__fish_seen_subcommand_from timeline" -l svg-height -x -d "Image height"
+complete -c hyprlog -n "__fish_seen_subcommand_from timeline" -l no-labels -d "Leave out the axis labels"
+complete -c hyprlog -n "__fish_seen_subcommand_from bar" -l follow -d "Print a line on every focus change"
+complete -c hyprlog -l no-config -d "Ignore hyprlog.conf"
+complete -c hyprlog -l idle -d "Report an idle event"
+complete -c hyprlog -l resume -d "Report a resume event"
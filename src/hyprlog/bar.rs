@@ -0,0 +1,271 @@
+use crate::Settings;
+use crate::interval::Interval;
+use crate::log_parsing::{ParsedLog, compute_durations, key, parse_log};
+use crate::log_reader::{LogReader, today_file};
+use crate::sockets::SNITCH_SOCKET;
+use crate::svg::escape;
+use crate::view::{other_row_name, split_tail};
+use chrono::Utc;
+use serde_json::{Value, json};
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::net::UnixDatagram;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how often the day file is checked for new rows
+const POLL: Duration = Duration::from_secs(1);
+const REFRESH: Duration = Duration::from_secs(60);
+/// Rows in the tooltip, unless --top or --all say otherwise
+pub const BAR_TOP: usize = 5;
+
+/// Prints today's activity as one line of JSON for a Waybar custom module with
+/// `"return-type": "json"`
+pub fn print_status(settings: &mut Settings) {
+    match status(settings) {
+        Ok(status) => println!("{status}"),
+        Err(e) => {
+            eprintln!("Failed to parse log: {e:?}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Prints a new status on every focus change and once a minute, until the bar kills it. Focus
+/// changes come from the snitch socket, or from the day file growing when hyprlogd isn't snitching.
+pub fn follow(settings: &mut Settings) {
+    let socket = bind_snitch_socket();
+    if let Some(socket) = &socket {
+        let _ = socket.set_read_timeout(Some(POLL));
+    }
+    let mut buf = [0; 4096];
+    let mut file_len = None;
+    let mut printed: Option<Instant> = None;
+    loop {
+        let len = fs::metadata(today_file()).map(|m| m.len()).ok();
+        if len != file_len || printed.is_none_or(|t| t.elapsed() >= REFRESH) {
+            file_len = len;
+            printed = Some(Instant::now());
+            match status(settings) {
+                Ok(status) => println!("{status}"),
+                Err(e) => eprintln!("Failed to parse log: {e:?}"),
+            }
+        }
+        // the row is already in the file when its datagram arrives
+        match &socket {
+            Some(socket) => match socket.recv(&mut buf) {
+                Ok(_) => file_len = None,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(_) => thread::sleep(POLL),
+            },
+            None => thread::sleep(POLL),
+        }
+    }
+}
+
+// a socket file nobody listens on was left behind by a bar that got killed. Another bar that's
+// still running keeps its socket, and this one watches the day file instead.
+fn bind_snitch_socket() -> Option<UnixDatagram> {
+    match UnixDatagram::bind(SNITCH_SOCKET) {
+        Ok(socket) => Some(socket),
+        Err(e) if e.kind() == ErrorKind::AddrInUse => {
+            let probe = UnixDatagram::unbound().ok()?;
+            let refused = probe
+                .send_to(&[], SNITCH_SOCKET)
+                .is_err_and(|e| e.kind() == ErrorKind::ConnectionRefused);
+            if !refused {
+                return None;
+            }
+            fs::remove_file(SNITCH_SOCKET).ok()?;
+            UnixDatagram::bind(SNITCH_SOCKET).ok()
+        }
+        Err(_) => None,
+    }
+}
+
+fn status(settings: &mut Settings) -> Result<Value, Box<dyn Error>> {
+    // following runs past midnight
+    settings.interval = Interval::default();
+    let now = Utc::now().timestamp_millis();
+    let mut reader = LogReader::new(settings);
+    let log = parse_log(&mut reader, settings)?;
+    Ok(log_status(settings, &log, now))
+}
+
+// the status of a log parsed at `now`
+fn log_status(settings: &Settings, log: &ParsedLog, now: i64) -> Value {
+    let (durations, total) = compute_durations(&log.spans, settings);
+
+    // parse_log closes the open span when it runs, that's the window focused right now
+    let current = log
+        .spans
+        .last()
+        .filter(|span| span.end >= now)
+        .and_then(|span| {
+            Some((
+                key(settings, &span.class, &span.title)?,
+                span.end - span.start,
+            ))
+        });
+    let (text, class, percentage) = match current {
+        Some((key, duration)) => {
+            let today = durations
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map_or(0, |(_, duration)| *duration);
+            let text = format!(
+                "{} {} · {}",
+                escape(&key),
+                short_duration(duration as u64),
+                short_duration(total)
+            );
+            let percentage = match total {
+                0 => 0,
+                total => (100 * today / total) as u8,
+            };
+            (text, "active", percentage)
+        }
+        None => (format!("idle · {}", short_duration(total)), "idle", 0),
+    };
+
+    // exactly the rows --top keeps, and everything else in an Other row, even a single one, so
+    // the tooltip is never longer than --top + 1 and all of today's time is in it
+    let (mut rows, folded) = split_tail(durations, total, settings);
+    if !folded.is_empty() {
+        let duration = folded.iter().map(|(_, duration)| duration).sum();
+        rows.push((other_row_name(folded.len()), duration));
+    }
+    let mut tooltip = format!("Today {}", short_duration(total));
+    for (key, duration) in &rows {
+        tooltip.push_str(&format!("\n{}  {}", escape(key), short_duration(*duration)));
+    }
+
+    json!({
+        "text": text,
+        "tooltip": tooltip,
+        "class": class,
+        "percentage": percentage,
+    })
+}
+
+// minutes are enough for a bar that updates once a minute
+fn short_duration(ms: u64) -> String {
+    let minutes = ms / 60_000;
+    match minutes / 60 {
+        0 => format!("{minutes}m"),
+        hours => format!("{hours}h {:02}m", minutes % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::log_parsing::Span;
+
+    const MIN: i64 = 60_000;
+
+    // one span per class, back to back, the last one ending at `now`
+    fn log(classes: &[(&str, i64)], now: i64) -> ParsedLog {
+        let mut start = now
+            - classes
+                .iter()
+                .map(|(_, minutes)| minutes * MIN)
+                .sum::<i64>();
+        let spans = classes
+            .iter()
+            .map(|(class, minutes)| {
+                let span = Span {
+                    start,
+                    end: start + minutes * MIN,
+                    class: class.to_string(),
+                    title: String::new(),
+                };
+                start = span.end;
+                span
+            })
+            .collect();
+        ParsedLog {
+            spans,
+            ..Default::default()
+        }
+    }
+
+    fn bar_settings() -> Settings {
+        let mut settings = Settings::new(Config::without_file());
+        settings.top = Some(BAR_TOP);
+        settings
+    }
+
+    #[test]
+    fn short_durations_are_in_minutes() {
+        #[rustfmt::skip]
+        let cases = [
+            (0, "0m"),
+            (59_999, "0m"),
+            (60_000, "1m"),
+            (59 * MIN as u64, "59m"),
+            (60 * MIN as u64, "1h 00m"),
+            (125 * MIN as u64, "2h 05m"),
+            (25 * 60 * MIN as u64, "25h 00m"),
+        ];
+        for (ms, expected) in cases {
+            assert_eq!(short_duration(ms), expected, "{ms}");
+        }
+    }
+
+    #[test]
+    fn status_shows_the_focused_window() {
+        let now = 1_000 * MIN;
+        let log = log(&[("kitty", 30), ("firefox", 60), ("kitty", 10)], now);
+        let status = log_status(&bar_settings(), &log, now);
+        assert_eq!(status["text"], "kitty 10m · 1h 40m");
+        assert_eq!(status["class"], "active");
+        assert_eq!(status["percentage"], 40);
+        assert_eq!(
+            status["tooltip"],
+            "Today 1h 40m\nfirefox  1h 00m\nkitty  40m"
+        );
+    }
+
+    #[test]
+    fn status_is_idle_without_an_open_span() {
+        let now = 1_000 * MIN;
+        let log = log(&[("kitty", 30)], now - MIN);
+        let status = log_status(&bar_settings(), &log, now);
+        assert_eq!(status["text"], "idle · 30m");
+        assert_eq!(status["class"], "idle");
+        assert_eq!(status["percentage"], 0);
+    }
+
+    #[test]
+    fn tooltip_has_at_most_top_rows_and_other() {
+        let now = 1_000 * MIN;
+        let classes = [
+            ("a", 9),
+            ("b", 8),
+            ("c", 7),
+            ("d", 6),
+            ("e", 5),
+            ("f", 4),
+            ("g", 3),
+        ];
+        let cases: [(usize, &[&str]); 3] = [
+            (5, &["a", "b", "c", "d", "e"]),
+            // a single folded row still gets an Other row, rather than losing its time
+            (6, &["a", "b", "c", "d", "e", "Other (1)"]),
+            (7, &["a", "b", "c", "d", "e", "Other (2)"]),
+        ];
+        for (count, expected) in cases {
+            let status = log_status(&bar_settings(), &log(&classes[..count], now), now);
+            let tooltip = status["tooltip"].as_str().unwrap();
+            let keys: Vec<&str> = tooltip
+                .lines()
+                .skip(1)
+                .map(|line| line.split("  ").next().unwrap())
+                .collect();
+            assert_eq!(keys, expected, "{count} classes");
+        }
+    }
+}
@@ -1,105 +1,1470 @@
+use colored::Color;
 use directories::BaseDirs;
-use std::{collections::HashMap, fs, path::PathBuf};
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    GroupBy, OutputFormat, SortOrder,
+    config_file::{
+        Diagnostic, Line, ParseError, Value, display_path, expand_path, hostname, parse_line,
+    },
+    glyphs::{GLYPH_SETS, Glyphs},
+};
+
+// applied after the rules from hyprlog.conf, so users can override them. Any class containing
+// steam_app is a game, the same as before rewrite rules existed.
+const DEFAULT_CLASS_REWRITES: &[(&str, &str)] = &[(r"^.*steam_app.*$", "steam")];
+// lockers and screensavers, focus on these counts as idle time
+const DEFAULT_IDLE_CLASSES: &[&str] = &["hyprlock", "swaylock", "gtklock", "waylock"];
+// games and video players, idle rows are ignored while these are focused
+const DEFAULT_IDLE_INHIBIT_CLASSES: &[&str] = &["steam_app_*", "mpv"];
+// timeline and table colours, replaced by `palette`
+const DEFAULT_PALETTE: &[Color] = &[
+    Color::Green,
+    Color::Red,
+    Color::Blue,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Cyan,
+    Color::BrightRed,
+    Color::BrightGreen,
+    Color::BrightBlue,
+    Color::BrightMagenta,
+    Color::BrightYellow,
+    Color::BrightCyan,
+];
+// colour blind safe palettes, from Okabe & Ito and Paul Tol
+const NAMED_PALETTES: &[(&str, &[&str])] = &[
+    (
+        "okabe-ito",
+        &[
+            "#e69f00", "#56b4e9", "#009e73", "#f0e442", "#0072b2", "#d55e00", "#cc79a7",
+        ],
+    ),
+    (
+        "tol-bright",
+        &[
+            "#4477aa", "#ee6677", "#228833", "#ccbb44", "#66ccee", "#aa3377", "#bbbbbb",
+        ],
+    ),
+    (
+        "tol-vibrant",
+        &[
+            "#0077bb", "#33bbee", "#009988", "#ee7733", "#cc3311", "#ee3377", "#bbbbbb",
+        ],
+    ),
+];
+const COLOR_NAMES: &[(&str, Color)] = &[
+    ("black", Color::Black),
+    ("red", Color::Red),
+    ("green", Color::Green),
+    ("yellow", Color::Yellow),
+    ("blue", Color::Blue),
+    ("magenta", Color::Magenta),
+    ("cyan", Color::Cyan),
+    ("white", Color::White),
+    ("bright_black", Color::BrightBlack),
+    ("bright_red", Color::BrightRed),
+    ("bright_green", Color::BrightGreen),
+    ("bright_yellow", Color::BrightYellow),
+    ("bright_blue", Color::BrightBlue),
+    ("bright_magenta", Color::BrightMagenta),
+    ("bright_cyan", Color::BrightCyan),
+    ("bright_white", Color::BrightWhite),
+];
 
 pub struct Config {
     pub class_mappings: HashMap<String, String>, //<Tz>,
+    pub class_rewrites: Vec<(Regex, String)>,
+    pub title_rewrites: Vec<(Pattern, Regex, String)>,
+    pub categories: Vec<(String, Vec<CategoryMatcher>)>,
+    pub ignore_classes: Vec<Pattern>,
+    pub ignore_titles: Vec<Pattern>,
+    pub ignore_mode: IgnoreMode,
+    pub idle_classes: Vec<Pattern>,
+    pub idle_inhibit_classes: Vec<Pattern>,
+    pub max_span: Option<u64>, // ms
+    pub min_span: Option<u64>, // ms
+    pub defaults: ReportOptions,
+    pub presets: Vec<(String, ReportOptions)>,
+    pub palette: Vec<Color>,
+    /// Colours for specific classes, categories or titles, these skip the palette
+    pub colors: HashMap<String, Color>,
+    /// Detected from the terminal when not set
+    pub glyphs: Option<Glyphs>,
+}
+
+/// Report options that can be set in the config, the command line arguments override them
+#[derive(Clone, Default)]
+pub struct ReportOptions {
+    pub full: Option<bool>,
+    pub multi: Option<bool>,
+    pub stacked: Option<bool>,
+    pub patterns: Option<bool>,
+    pub days: Option<u64>,
+    pub class: Option<String>,
+    pub by: Option<GroupBy>,
+    pub sort: Option<SortOrder>,
+    pub format: Option<OutputFormat>,
+    pub top: Option<usize>,
+    pub min_percent: Option<f64>,
+}
+
+const REPORT_OPTIONS: &[&str] = &[
+    "full",
+    "multi",
+    "stacked",
+    "patterns",
+    "days",
+    "class",
+    "by",
+    "sort",
+    "format",
+    "top",
+    "min_percent",
+];
+
+impl ReportOptions {
+    /// Returns None if the directive isn't a report option
+    fn parse(&mut self, directive: &Value, values: &[Value]) -> Option<Result<(), ParseError>> {
+        let option = directive.text.as_str();
+        if !REPORT_OPTIONS.contains(&option) {
+            return None;
+        }
+        Some(match values {
+            [value] => self
+                .set(option, &value.text)
+                .map_err(|e| ParseError::new(value.column, e)),
+            _ => Err(ParseError::new(
+                directive.column,
+                format!("{option} takes exactly one value."),
+            )),
+        })
+    }
+
+    fn set(&mut self, option: &str, value: &str) -> Result<(), String> {
+        match option {
+            "full" => self.full = Some(parse_bool(value)?),
+            "multi" => self.multi = Some(parse_bool(value)?),
+            "stacked" => self.stacked = Some(parse_bool(value)?),
+            "patterns" => self.patterns = Some(parse_bool(value)?),
+            "days" => {
+                let days = value.parse::<u64>().ok().filter(|days| *days > 0);
+                self.days = Some(days.ok_or("days should be a number of days.")?);
+            }
+            "class" => self.class = Some(value.to_string()),
+            "by" => {
+                self.by = Some(match value {
+                    "class" => GroupBy::Class,
+                    "category" => GroupBy::Category,
+                    _ => return Err(String::from("by should be \"class\" or \"category\".")),
+                })
+            }
+            "sort" => {
+                self.sort = Some(match value {
+                    "duration" => SortOrder::Duration,
+                    "name" => SortOrder::Name,
+                    _ => return Err(String::from("sort should be \"duration\" or \"name\".")),
+                })
+            }
+            "format" => {
+                self.format = Some(OutputFormat::parse(value).ok_or(
+                    "format should be \"terminal\", \"json\", \"csv\", \"tsv\" or \"markdown\".",
+                )?)
+            }
+            "top" => {
+                self.top = Some(
+                    value
+                        .parse()
+                        .map_err(|_| "top should be a number of rows.")?,
+                )
+            }
+            "min_percent" => {
+                self.min_percent = Some(
+                    parse_percent(value)
+                        .ok_or("min_percent should be a percentage from 0 to 100.")?,
+                )
+            }
+            _ => unreachable!("{option} is listed in REPORT_OPTIONS"),
+        }
+        Ok(())
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("Expected true or false, found \"{value}\".")),
+    }
+}
+
+/// A percentage from 0 to 100, `NaN` would keep or fold every row
+pub fn parse_percent(value: &str) -> Option<f64> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|percent| (0.0..=100.0).contains(percent))
+}
+
+/// What happens to the time spent in an ignored window
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum IgnoreMode {
+    /// The ignored focus event is skipped, the previously focused window keeps the time
+    Drop,
+    /// The time is not credited to anything, like while idle
+    Idle,
+}
+
+pub const UNCATEGORIZED: &str = "uncategorized";
+
+/// One entry of a category, `title:` prefixed patterns match the title instead of the class
+pub enum CategoryMatcher {
+    Class(Pattern),
+    Title(Pattern),
+}
+
+impl CategoryMatcher {
+    fn new(pattern: &str) -> Self {
+        match pattern.strip_prefix("title:") {
+            Some(title) => Self::Title(Pattern::new(title)),
+            None => Self::Class(Pattern::new(pattern)),
+        }
+    }
+
+    fn matches(&self, class: &str, title: &str) -> bool {
+        match self {
+            Self::Class(pattern) => pattern.matches(class),
+            Self::Title(pattern) => pattern.matches(title),
+        }
+    }
+}
+
+/// A glob style pattern, `*` matches any number of characters and `?` matches exactly one
+pub struct Pattern {
+    glob: String,
+    regex: Regex,
+}
+
+impl Pattern {
+    pub fn new(glob: &str) -> Self {
+        let mut regex = String::from("^");
+        for ch in glob.chars() {
+            match ch {
+                '*' => regex.push_str(".*"),
+                '?' => regex.push('.'),
+                ch => regex.push_str(&regex::escape(&ch.to_string())),
+            }
+        }
+        regex.push('$');
+        Self {
+            glob: glob.to_string(),
+            regex: Regex::new(&regex).expect("escaped glob is always a valid regex"),
+        }
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.glob
+    }
 }
 
 impl Config {
+    /// The config from hyprlog.conf, problems in the file are printed to stderr
     pub fn new() -> Self {
+        let (config, diagnostics) = Self::load();
+        for diagnostic in diagnostics {
+            eprintln!("{diagnostic}");
+        }
+        config
+    }
+
+    /// The config from hyprlog.conf along with every problem found in it
+    pub fn load() -> (Self, Vec<Diagnostic>) {
+        let mut config = Self::default();
+        let diagnostics = config.parse_config();
+        config.add_builtin_rules();
+        (config, diagnostics)
+    }
+
+    /// Only the built in rules, for --no-config
+    pub fn without_file() -> Self {
         let mut config = Self::default();
-        config.parse_config();
+        config.add_builtin_rules();
         config
     }
 
+    fn add_builtin_rules(&mut self) {
+        for (pattern, replacement) in DEFAULT_CLASS_REWRITES {
+            self.class_rewrites.push((
+                Regex::new(pattern).expect("invalid default class rewrite"),
+                replacement.to_string(),
+            ));
+        }
+        self.idle_classes
+            .extend(DEFAULT_IDLE_CLASSES.iter().map(|p| Pattern::new(p)));
+        self.idle_inhibit_classes
+            .extend(DEFAULT_IDLE_INHIBIT_CLASSES.iter().map(|p| Pattern::new(p)));
+    }
+
     fn default() -> Self {
         Self {
             class_mappings: HashMap::new(),
+            class_rewrites: Vec::new(),
+            title_rewrites: Vec::new(),
+            categories: Vec::new(),
+            ignore_classes: Vec::new(),
+            ignore_titles: Vec::new(),
+            ignore_mode: IgnoreMode::Idle,
+            idle_classes: Vec::new(),
+            idle_inhibit_classes: Vec::new(),
+            max_span: None,
+            min_span: None,
+            defaults: ReportOptions::default(),
+            presets: Vec::new(),
+            palette: DEFAULT_PALETTE.to_vec(),
+            colors: HashMap::new(),
+            glyphs: None,
+        }
+    }
+
+    /// Rename a class using the aliases first, then the first matching rewrite rule. SYSTEM rows are
+    /// left alone.
+    pub fn map_class(&self, class: &str) -> String {
+        // hyprlogd's own events, the rules are only meant for windows
+        if class == "SYSTEM" {
+            return class.to_string();
+        }
+        if let Some(alias) = self.class_mappings.get(class) {
+            return alias.clone();
         }
+        for (regex, replacement) in &self.class_rewrites {
+            if regex.is_match(class) {
+                return regex.replace(class, replacement.as_str()).into_owned();
+            }
+        }
+        class.to_string()
+    }
+
+    /// Apply every title rewrite rule whose class pattern matches the (already mapped) class. Titles
+    /// that no rule changes are kept exactly as logged.
+    pub fn map_title(&self, class: &str, title: &str) -> String {
+        // the title of a SYSTEM row is the event, like idle or resume
+        if class == "SYSTEM" {
+            return title.to_string();
+        }
+        let mut title = title.to_string();
+        for (class_pattern, regex, replacement) in &self.title_rewrites {
+            if class_pattern.matches(class) && regex.is_match(&title) {
+                // a rule that cut off a prefix or suffix usually leaves a space behind
+                title = regex
+                    .replace_all(&title, replacement.as_str())
+                    .trim()
+                    .to_string();
+            }
+        }
+        title
+    }
+
+    /// The first category with a pattern matching the class or title
+    pub fn category(&self, class: &str, title: &str) -> &str {
+        self.categories
+            .iter()
+            .find(|(_, matchers)| matchers.iter().any(|m| m.matches(class, title)))
+            .map(|(name, _)| name.as_str())
+            .unwrap_or(UNCATEGORIZED)
+    }
+
+    pub fn is_idle_class(&self, class: &str) -> bool {
+        self.idle_classes.iter().any(|p| p.matches(class))
+    }
+
+    pub fn inhibits_idle(&self, class: &str) -> bool {
+        self.idle_inhibit_classes.iter().any(|p| p.matches(class))
+    }
+
+    pub fn preset(&self, name: &str) -> Option<&ReportOptions> {
+        self.presets
+            .iter()
+            .find(|(preset, _)| preset == name)
+            .map(|(_, options)| options)
     }
 
-    fn parse_config(&mut self) {
-        let config_path = BaseDirs::new()
-            .map(|b| b.config_dir().to_path_buf())
-            .unwrap_or_else(|| PathBuf::new())
-            .join("hypr/hyprlog.conf");
+    pub fn is_ignored(&self, class: &str, title: &str) -> bool {
+        self.ignore_classes.iter().any(|p| p.matches(class))
+            || self.ignore_titles.iter().any(|p| p.matches(title))
+    }
+
+    /// Reads hyprlog.conf and the files it sources into the config, every problem found is
+    /// returned instead of printed
+    fn parse_config(&mut self) -> Vec<Diagnostic> {
+        let config_path = config_path();
+        let mut diagnostics = Vec::new();
         if config_path.exists() {
-            let config = fs::read_to_string(config_path).expect("Failed to read hyprlog.conf.");
-            let mut line_number = 0;
-            for line in config.lines() {
-                line_number += 1;
-                if line.starts_with('#') {
-                    continue;
+            let host = hostname();
+            self.parse_file(&config_path, &host, &mut Vec::new(), &mut diagnostics);
+        }
+        diagnostics
+    }
+
+    // `sourcing` holds the files currently being read, to catch include cycles
+    fn parse_file(
+        &mut self,
+        path: &Path,
+        host: &str,
+        sourcing: &mut Vec<PathBuf>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let config = match fs::read_to_string(path) {
+            Ok(config) => config,
+            Err(e) => {
+                diagnostics.push(Diagnostic::new(
+                    path,
+                    0,
+                    ParseError::new(0, format!("Failed to read the file: {e}")),
+                ));
+                return;
+            }
+        };
+        sourcing.push(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+
+        // every file starts outside of any section, sections end at the end of their file
+        let mut block = Block::Global;
+        for (index, line) in config.lines().enumerate() {
+            let result = match parse_line(line) {
+                Ok(None) => Ok(()),
+                Ok(Some(Line::Section { name, args })) => {
+                    let result = self.section(name, args, host);
+                    // lines of a block with a broken header are skipped instead of applying globally
+                    block = *result.as_ref().unwrap_or(&Block::Skip);
+                    result.map(|_| ())
                 }
-                // removes comments and leading or trailing whitespace
-                let trimmed_line = line.trim().split('#').next().unwrap();
-
-                if trimmed_line.starts_with("class_alias") {
-                    let equals_split: Vec<&str> = trimmed_line.split("=").collect();
-                    if equals_split.len() == 1 {
-                        println!(
-                            "Failed to parse hyprlog.conf at line {}: Missing '='.",
-                            line_number
-                        );
-                        continue;
-                    } else if equals_split.len() > 2 {
-                        println!(
-                            "Failed to parse hyprlog.conf at line {}: Multiple '='.",
-                            line_number
-                        );
-                        continue;
+                Ok(Some(Line::Directive { .. })) if block == Block::Skip => Ok(()),
+                Ok(Some(Line::Directive { name, values })) if name.text == "source" => {
+                    match block {
+                        Block::Preset(_) => Err(ParseError::new(
+                            name.column,
+                            "Files can't be sourced from a preset.",
+                        )),
+                        _ => {
+                            self.source(path, index + 1, host, name, values, sourcing, diagnostics)
+                        }
                     }
-                    let values: Vec<&str> = equals_split[1].split(",").collect();
-                    if values.len() == 1 {
-                        println!(
-                            "Failed to parse hyprlog.conf at line {}: Missing ','.",
-                            line_number
-                        );
-                        continue;
-                    } else if equals_split.len() > 2 {
-                        println!(
-                            "Failed to parse hyprlog.conf at line {}: Multiple ',', there should only be two values per alias.",
-                            line_number
-                        );
-                        continue;
+                }
+                Ok(Some(Line::Directive { name, values })) => match block {
+                    Block::Preset(index) => self.directive(Some(index), name, values),
+                    _ => self.directive(None, name, values),
+                },
+                Err(e) => {
+                    // a header that doesn't parse still starts a block, its lines are skipped too
+                    if line.trim_start().starts_with('[') {
+                        block = Block::Skip;
                     }
+                    Err(e)
+                }
+            };
+            if let Err(e) = result {
+                diagnostics.push(Diagnostic::new(path, index + 1, e));
+            }
+        }
+        sourcing.pop();
+    }
 
-                    let class = values[0]
-                        .trim()
-                        .strip_prefix('"')
-                        .and_then(|s| s.strip_suffix('"'));
-                    if class.is_none() {
-                        println!(
-                            "Failed to parse hyprlog.conf at line {}: Class string is not properly quoted.",
-                            line_number
-                        );
-                        continue;
-                    }
-                    let alias = values[1]
-                        .trim()
-                        .strip_prefix('"')
-                        .and_then(|s| s.strip_suffix('"'));
-                    if alias.is_none() {
-                        println!(
-                            "Failed to parse hyprlog.conf at line {}: Alias string is not properly quoted.",
-                            line_number
-                        );
-                        continue;
-                    }
+    // reads every file matching the values of a `source = ...` line on line `line` of `path`. A
+    // value that can't be sourced is reported on its own, the other values are still read.
+    #[allow(clippy::too_many_arguments)]
+    fn source(
+        &mut self,
+        path: &Path,
+        line: usize,
+        host: &str,
+        name: Value,
+        values: Vec<Value>,
+        sourcing: &mut Vec<PathBuf>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<(), ParseError> {
+        if values.is_empty() {
+            return Err(ParseError::new(name.column, "source needs a path."));
+        }
+        let base = path.parent().unwrap_or(Path::new(""));
+        let mut files = Vec::new();
+        for value in &values {
+            let matches = match expand_path(&value.text, base) {
+                Ok(matches) => matches,
+                Err(e) => {
+                    diagnostics.push(Diagnostic::new(
+                        path,
+                        line,
+                        ParseError::new(value.column, e),
+                    ));
+                    continue;
+                }
+            };
+            for file in matches {
+                let canonical = file.canonicalize().unwrap_or_else(|_| file.clone());
+                if sourcing.contains(&canonical) {
+                    let message = format!(
+                        "{} is already being read, sourcing it again would never end.",
+                        display_path(&canonical)
+                    );
+                    diagnostics.push(Diagnostic::new(
+                        path,
+                        line,
+                        ParseError::new(value.column, message),
+                    ));
+                    continue;
+                }
+                files.push(file);
+            }
+        }
+        for file in files {
+            self.parse_file(&file, host, sourcing, diagnostics);
+        }
+        Ok(())
+    }
 
-                    self.class_mappings
-                        .insert(class.unwrap().to_string(), alias.unwrap().to_string());
-                } else {
-                    if !line.is_empty() {
-                        println!(
-                            "Failed to parse hyprlog.conf at line {}: Unknown symbol {}",
-                            line_number,
-                            line.split_whitespace().next().unwrap()
-                        );
+    fn section(&mut self, name: Value, args: Vec<Value>, host: &str) -> Result<Block, ParseError> {
+        match name.text.as_str() {
+            "preset" => {
+                let [preset] = exactly(args, name.column, "A preset needs exactly one name.")?;
+                if self.preset(&preset.text).is_some() {
+                    return Err(ParseError::new(
+                        preset.column,
+                        format!("The preset {} is already defined.", preset.text),
+                    ));
+                }
+                self.presets.push((preset.text, ReportOptions::default()));
+                Ok(Block::Preset(self.presets.len() - 1))
+            }
+            "host" => {
+                if args.is_empty() {
+                    return Err(ParseError::new(
+                        name.column,
+                        "A host block needs at least one hostname.",
+                    ));
+                }
+                let matches = args.iter().any(|a| Pattern::new(&a.text).matches(host));
+                Ok(if matches { Block::Global } else { Block::Skip })
+            }
+            other => Err(ParseError::new(
+                name.column,
+                format!("Unknown section {other}, expected [preset NAME] or [host HOSTNAME]."),
+            )),
+        }
+    }
+
+    fn directive(
+        &mut self,
+        preset: Option<usize>,
+        name: Value,
+        values: Vec<Value>,
+    ) -> Result<(), ParseError> {
+        let options = match preset {
+            Some(index) => &mut self.presets[index].1,
+            None => &mut self.defaults,
+        };
+        if let Some(result) = options.parse(&name, &values) {
+            return result;
+        }
+        if let Some(index) = preset {
+            return Err(ParseError::new(
+                name.column,
+                format!(
+                    "Only report options can be set in [preset {}], a preset runs until the next section or the end of the file.",
+                    self.presets[index].0
+                ),
+            ));
+        }
+
+        match name.text.as_str() {
+            "class_alias" => {
+                let [class, alias] = exactly(
+                    values,
+                    name.column,
+                    "There should be exactly two values per alias.",
+                )?;
+                self.class_mappings.insert(class.text, alias.text);
+            }
+            "class_rewrite" => {
+                let [pattern, replacement] = exactly(
+                    values,
+                    name.column,
+                    "There should be exactly two values per rewrite, a regex and a replacement.",
+                )?;
+                self.class_rewrites
+                    .push((parse_regex(&pattern)?, replacement.text));
+            }
+            "title_rewrite" => {
+                let [class, pattern, replacement] = exactly(
+                    values,
+                    name.column,
+                    "There should be exactly three values per title rewrite, a class pattern, a regex and a replacement.",
+                )?;
+                self.title_rewrites.push((
+                    Pattern::new(&class.text),
+                    parse_regex(&pattern)?,
+                    replacement.text,
+                ));
+            }
+            "category" => {
+                if values.len() < 2 {
+                    return Err(ParseError::new(
+                        name.column,
+                        "A category needs a name and at least one pattern.",
+                    ));
+                }
+                let mut values = values.into_iter();
+                let category = values.next().unwrap().text;
+                let matchers = values.map(|p| CategoryMatcher::new(&p.text)).collect();
+                self.categories.push((category, matchers));
+            }
+            "ignore_class" => self.ignore_classes.extend(patterns(&values)),
+            "ignore_title" => self.ignore_titles.extend(patterns(&values)),
+            "idle_class" => self.idle_classes.extend(patterns(&values)),
+            "idle_inhibit_class" => self.idle_inhibit_classes.extend(patterns(&values)),
+            "max_span" => {
+                let [value] = exactly(values, name.column, "max_span takes exactly one value.")?;
+                match parse_duration(&value.text) {
+                    Some(max_span) if max_span > 0 => self.max_span = Some(max_span),
+                    _ => {
+                        return Err(ParseError::new(
+                            value.column,
+                            "max_span should be a duration like \"30m\" or \"1h30m\".",
+                        ));
                     }
                 }
-                continue;
             }
+            "min_span" => {
+                let [value] = exactly(values, name.column, "min_span takes exactly one value.")?;
+                let min_span = parse_duration(&value.text).ok_or_else(|| {
+                    ParseError::new(value.column, "min_span should be a duration like \"2s\".")
+                })?;
+                self.min_span = Some(min_span);
+            }
+            "palette" => {
+                let named = NAMED_PALETTES
+                    .iter()
+                    .find(|(palette, _)| values.len() == 1 && values[0].text == *palette);
+                self.palette = match named {
+                    Some((_, colors)) => colors.iter().filter_map(|hex| hex_color(hex)).collect(),
+                    None => values.iter().map(parse_color).collect::<Result<_, _>>()?,
+                };
+            }
+            "color" => {
+                let [key, color] = exactly(
+                    values,
+                    name.column,
+                    "There should be exactly two values per color, a name and a color.",
+                )?;
+                self.colors.insert(key.text, parse_color(&color)?);
+            }
+            "glyphs" => {
+                self.glyphs = Some(match &values[..] {
+                    [name] => Glyphs::named(&name.text).ok_or_else(|| {
+                        ParseError::new(
+                            name.column,
+                            format!(
+                                "glyphs should be one of {}, or four custom values.",
+                                GLYPH_SETS.join(", ")
+                            ),
+                        )
+                    })?,
+                    [left, right, center, idle] => {
+                        Glyphs::custom(&left.text, &right.text, &center.text, &idle.text)
+                            .map_err(|e| ParseError::new(left.column, e))?
+                    }
+                    _ => {
+                        return Err(ParseError::new(
+                            name.column,
+                            "glyphs takes the name of a set, or the left, right and center ramps and the idle glyph.",
+                        ));
+                    }
+                });
+            }
+            "ignore_mode" => {
+                let [value] = exactly(values, name.column, "ignore_mode takes exactly one value.")?;
+                self.ignore_mode = match value.text.as_str() {
+                    "drop" => IgnoreMode::Drop,
+                    "idle" => IgnoreMode::Idle,
+                    _ => {
+                        return Err(ParseError::new(
+                            value.column,
+                            "ignore_mode should be \"drop\" or \"idle\".",
+                        ));
+                    }
+                };
+            }
+            other => {
+                return Err(ParseError::new(
+                    name.column,
+                    format!("Unknown directive {other}."),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where the lines of a file currently go
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Block {
+    Global,
+    /// Inside [preset NAME], the index into Config::presets
+    Preset(usize),
+    /// Inside a [host HOSTNAME] block for another machine, or a block with a broken header
+    Skip,
+}
+
+pub fn config_path() -> PathBuf {
+    BaseDirs::new()
+        .map(|b| b.config_dir().to_path_buf())
+        .unwrap_or_default()
+        .join("hypr/hyprlog.conf")
+}
+
+// the values of a directive that takes a fixed number of them
+fn exactly<const N: usize>(
+    values: Vec<Value>,
+    column: usize,
+    message: &str,
+) -> Result<[Value; N], ParseError> {
+    <[Value; N]>::try_from(values).map_err(|_| ParseError::new(column, message))
+}
+
+fn parse_regex(value: &Value) -> Result<Regex, ParseError> {
+    Regex::new(&value.text).map_err(|e| {
+        ParseError::new(
+            value.column,
+            format!("Invalid regex \"{}\": {}", value.text, e),
+        )
+    })
+}
+
+fn patterns(values: &[Value]) -> impl Iterator<Item = Pattern> + '_ {
+    values.iter().map(|v| Pattern::new(&v.text))
+}
+
+/// Parses durations like "90s", "30m" or "1h30m" into milliseconds. Every number needs a unit, and
+/// durations too long to count in milliseconds are rejected.
+pub fn parse_duration(value: &str) -> Option<u64> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for ch in value.chars() {
+        if ch.is_ascii_digit() {
+            number.push(ch);
+            continue;
+        }
+        let unit = match ch {
+            'h' => 3_600_000,
+            'm' => 60_000,
+            's' => 1_000,
+            _ => return None,
+        };
+        let part = number.parse::<u64>().ok()?.checked_mul(unit)?;
+        total = total.checked_add(part)?;
+        number.clear();
+    }
+    number.is_empty().then_some(total)
+}
+
+/// Parses a colour name like "red" or "bright_blue", a 256 colour number or a "#rrggbb" hex
+/// colour. colored has no 256 colour escape, so numbers past 15 become their RGB value, and like
+/// hex colours terminals without truecolor support show the closest of the 16 named colours.
+fn parse_color(value: &Value) -> Result<Color, ParseError> {
+    let text = value.text.to_lowercase().replace(' ', "_");
+    if let Some((_, color)) = COLOR_NAMES.iter().find(|(name, _)| *name == text) {
+        return Ok(*color);
+    }
+    if let Ok(index) = text.parse::<u8>() {
+        return Ok(ansi_256_color(index));
+    }
+    match hex_color(&text) {
+        Some(color) => Ok(color),
+        None => Err(ParseError::new(
+            value.column,
+            format!(
+                "Invalid color \"{}\", expected a name like \"bright_red\", a number up to 255 or \"#rrggbb\".",
+                value.text
+            ),
+        )),
+    }
+}
+
+fn hex_color(text: &str) -> Option<Color> {
+    let rgb = text
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6)
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())?;
+    Some(Color::TrueColor {
+        r: (rgb >> 16) as u8,
+        g: (rgb >> 8) as u8,
+        b: rgb as u8,
+    })
+}
+
+// the 256 colour palette is the 16 named colours, a 6x6x6 colour cube and 24 shades of grey
+fn ansi_256_color(index: u8) -> Color {
+    match index {
+        0..16 => COLOR_NAMES[index as usize].1,
+        16..232 => {
+            let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+            let index = index - 16;
+            Color::TrueColor {
+                r: level(index / 36),
+                g: level(index / 6 % 6),
+                b: level(index % 6),
+            }
+        }
+        232.. => {
+            let grey = 8 + (index - 232) * 10;
+            Color::TrueColor {
+                r: grey,
+                g: grey,
+                b: grey,
+            }
+        }
+    }
+}
+
+fn color_setting(color: Color) -> String {
+    match COLOR_NAMES.iter().find(|(_, named)| *named == color) {
+        Some((name, _)) => quote(name),
+        None => match color {
+            Color::TrueColor { r, g, b } => quote(&format!("#{r:02x}{g:02x}{b:02x}")),
+            _ => unreachable!("every other color is named"),
+        },
+    }
+}
+
+// a value as it would be written in hyprlog.conf
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn quote_all<'a>(values: impl IntoIterator<Item = &'a str>) -> String {
+    values.into_iter().map(quote).collect::<Vec<_>>().join(", ")
+}
+
+// the inverse of parse_duration, 5400000 -> "1h30m"
+fn duration_setting(ms: u64) -> String {
+    let seconds = ms / 1000;
+    let parts = [
+        (seconds / 3600, 'h'),
+        (seconds / 60 % 60, 'm'),
+        (seconds % 60, 's'),
+    ];
+    let mut setting = String::new();
+    for (value, unit) in parts.into_iter().filter(|(value, _)| *value > 0) {
+        setting.push_str(&format!("{value}{unit}"));
+    }
+    if setting.is_empty() {
+        setting.push_str("0s");
+    }
+    quote(&setting)
+}
+
+/// Writes the effective config, including the built in rules, in the hyprlog.conf format
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut aliases: Vec<_> = self.class_mappings.iter().collect();
+        aliases.sort();
+        for (class, alias) in aliases {
+            writeln!(f, "class_alias = {}", quote_all([class.as_str(), alias]))?;
+        }
+        for (regex, replacement) in &self.class_rewrites {
+            writeln!(
+                f,
+                "class_rewrite = {}",
+                quote_all([regex.as_str(), replacement])
+            )?;
+        }
+        for (class, regex, replacement) in &self.title_rewrites {
+            writeln!(
+                f,
+                "title_rewrite = {}",
+                quote_all([class.as_str(), regex.as_str(), replacement])
+            )?;
+        }
+        for (name, matchers) in &self.categories {
+            let patterns: Vec<String> = matchers
+                .iter()
+                .map(|matcher| match matcher {
+                    CategoryMatcher::Class(pattern) => pattern.as_str().to_string(),
+                    CategoryMatcher::Title(pattern) => format!("title:{}", pattern.as_str()),
+                })
+                .collect();
+            writeln!(
+                f,
+                "category = {}, {}",
+                quote(name),
+                quote_all(patterns.iter().map(String::as_str))
+            )?;
+        }
+        for (directive, patterns) in [
+            ("ignore_class", &self.ignore_classes),
+            ("ignore_title", &self.ignore_titles),
+            ("idle_class", &self.idle_classes),
+            ("idle_inhibit_class", &self.idle_inhibit_classes),
+        ] {
+            if !patterns.is_empty() {
+                writeln!(
+                    f,
+                    "{directive} = {}",
+                    quote_all(patterns.iter().map(Pattern::as_str))
+                )?;
+            }
+        }
+        let ignore_mode = match self.ignore_mode {
+            IgnoreMode::Drop => "drop",
+            IgnoreMode::Idle => "idle",
+        };
+        writeln!(f, "ignore_mode = {}", quote(ignore_mode))?;
+        if let Some(max_span) = self.max_span {
+            writeln!(f, "max_span = {}", duration_setting(max_span))?;
+        }
+        if let Some(min_span) = self.min_span {
+            writeln!(f, "min_span = {}", duration_setting(min_span))?;
+        }
+        let palette: Vec<String> = self.palette.iter().map(|c| color_setting(*c)).collect();
+        writeln!(f, "palette = {}", palette.join(", "))?;
+        let mut colors: Vec<_> = self.colors.iter().collect();
+        colors.sort_by_key(|(key, _)| key.as_str());
+        for (key, color) in colors {
+            writeln!(f, "color = {}, {}", quote(key), color_setting(*color))?;
+        }
+        if let Some(glyphs) = &self.glyphs {
+            let setting = glyphs.setting();
+            writeln!(
+                f,
+                "glyphs = {}",
+                quote_all(setting.iter().map(String::as_str))
+            )?;
+        }
+        write!(f, "{}", self.defaults)?;
+        for (name, options) in &self.presets {
+            writeln!(f, "\n[preset {name}]")?;
+            write!(f, "{options}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ReportOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = [
+            ("full", self.full),
+            ("multi", self.multi),
+            ("stacked", self.stacked),
+            ("patterns", self.patterns),
+        ];
+        for (option, value) in flags {
+            if let Some(value) = value {
+                writeln!(f, "{option} = {value}")?;
+            }
+        }
+        if let Some(days) = self.days {
+            writeln!(f, "days = {days}")?;
         }
+        if let Some(class) = &self.class {
+            writeln!(f, "class = {}", quote(class))?;
+        }
+        if let Some(by) = self.by {
+            let by = match by {
+                GroupBy::Class => "class",
+                GroupBy::Category => "category",
+            };
+            writeln!(f, "by = {}", quote(by))?;
+        }
+        if let Some(sort) = self.sort {
+            let sort = match sort {
+                SortOrder::Duration => "duration",
+                SortOrder::Name => "name",
+            };
+            writeln!(f, "sort = {}", quote(sort))?;
+        }
+        if let Some(format) = self.format {
+            writeln!(f, "format = {}", quote(format.name()))?;
+        }
+        if let Some(top) = self.top {
+            writeln!(f, "top = {top}")?;
+        }
+        if let Some(min_percent) = self.min_percent {
+            writeln!(f, "min_percent = {min_percent}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // writes the files into a directory of their own and parses the first one, the
+    // diagnostics name their file relative to that directory
+    fn parse_files(
+        name: &str,
+        files: &[(&str, &str)],
+    ) -> (Config, Vec<(String, usize, usize, String)>) {
+        let dir = crate::test_files::temp_dir(name, files);
+        let mut config = Config::default();
+        let mut diagnostics = Vec::new();
+        config.parse_file(
+            &dir.join(files[0].0),
+            "desktop",
+            &mut Vec::new(),
+            &mut diagnostics,
+        );
+        let dir = dir.canonicalize().unwrap();
+        let diagnostics = diagnostics
+            .into_iter()
+            .map(|d| {
+                let file = d.file.canonicalize().unwrap_or(d.file);
+                let file = file.strip_prefix(&dir).unwrap().display().to_string();
+                (file, d.line, d.column, d.message)
+            })
+            .collect();
+        fs::remove_dir_all(dir).unwrap();
+        (config, diagnostics)
+    }
+
+    // parses `text` as hyprlog.conf on the host "desktop", without the built in rules
+    fn parse(name: &str, text: &str) -> (Config, Vec<(usize, usize, String)>) {
+        let (config, diagnostics) = parse_files(name, &[("hyprlog.conf", text)]);
+        let diagnostics = diagnostics
+            .into_iter()
+            .map(|(_, line, column, message)| (line, column, message))
+            .collect();
+        (config, diagnostics)
+    }
+
+    fn category_names(config: &Config) -> Vec<&str> {
+        config
+            .categories
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn sourced_globs_are_read_in_order() {
+        let (config, diagnostics) = parse_files(
+            "source-glob",
+            &[
+                (
+                    "hyprlog.conf",
+                    "category = main, a\nsource = conf.d/*.conf\n",
+                ),
+                ("conf.d/b.conf", "category = b, b\n"),
+                (
+                    "conf.d/a.conf",
+                    "category = a, a\n[preset a]\nfull = true\n",
+                ),
+                ("conf.d/.hidden.conf", "category = hidden, h\n"),
+            ],
+        );
+        assert!(diagnostics.is_empty());
+        assert_eq!(category_names(&config), ["main", "a", "b"]);
+        assert_eq!(config.preset("a").unwrap().full, Some(true));
+        // the preset ended with its file
+        assert_eq!(config.defaults.full, None);
+    }
+
+    #[test]
+    fn source_cycles_are_caught() {
+        let (config, diagnostics) = parse_files(
+            "source-cycle",
+            &[
+                (
+                    "hyprlog.conf",
+                    "source = other.conf\nsource = hyprlog.conf\n",
+                ),
+                (
+                    "other.conf",
+                    "category = other, o\nsource = ./hyprlog.conf\n",
+                ),
+            ],
+        );
+        let lines: Vec<_> = diagnostics
+            .iter()
+            .map(|(file, line, ..)| (file.as_str(), *line))
+            .collect();
+        assert_eq!(lines, [("other.conf", 2), ("hyprlog.conf", 2)]);
+        assert!(
+            diagnostics[0]
+                .3
+                .ends_with("sourcing it again would never end.")
+        );
+        assert_eq!(category_names(&config), ["other"]);
+    }
+
+    #[test]
+    fn missing_sources_are_reported() {
+        let (_, diagnostics) = parse_files(
+            "source-missing",
+            &[(
+                "hyprlog.conf",
+                "source = missing.conf\nsource = *.txt\nsource =\n",
+            )],
+        );
+        assert_eq!(diagnostics.len(), 3);
+        assert!(diagnostics[0].3.ends_with("missing.conf doesn't exist."));
+        assert!(diagnostics[1].3.ends_with("*.txt doesn't match any files."));
+        assert_eq!(diagnostics[2].3, "Missing value.");
+    }
+
+    #[test]
+    fn bad_source_values_dont_stop_the_others() {
+        let (config, diagnostics) = parse_files(
+            "source-partial",
+            &[
+                (
+                    "hyprlog.conf",
+                    "source = missing.conf, a.conf, hyprlog.conf, *.txt, b.conf\n",
+                ),
+                ("a.conf", "category = a, a\n"),
+                ("b.conf", "category = b, b\n"),
+            ],
+        );
+        let columns: Vec<_> = diagnostics
+            .iter()
+            .map(|(file, line, column, _)| (file.as_str(), *line, *column))
+            .collect();
+        assert_eq!(
+            columns,
+            [
+                ("hyprlog.conf", 1, 10),
+                ("hyprlog.conf", 1, 32),
+                ("hyprlog.conf", 1, 46)
+            ]
+        );
+        assert_eq!(category_names(&config), ["a", "b"]);
+    }
+
+    #[test]
+    fn sources_of_other_hosts_are_skipped() {
+        let (config, diagnostics) = parse_files(
+            "source-host",
+            &[
+                (
+                    "hyprlog.conf",
+                    "[host laptop]\nsource = laptop.conf\n[host desk*]\nsource = desktop.conf\n",
+                ),
+                ("desktop.conf", "category = desktop, d\n"),
+            ],
+        );
+        assert!(diagnostics.is_empty());
+        assert_eq!(category_names(&config), ["desktop"]);
+    }
+
+    #[test]
+    fn errors_have_the_line_and_column() {
+        let (config, diagnostics) = parse(
+            "errors",
+            "# comment\nclass_alias = a\n  bogus = 1\nmax_span = \"5x\"\n",
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                (
+                    2,
+                    1,
+                    String::from("There should be exactly two values per alias.")
+                ),
+                (3, 3, String::from("Unknown directive bogus.")),
+                (
+                    4,
+                    12,
+                    String::from("max_span should be a duration like \"30m\" or \"1h30m\".")
+                ),
+            ]
+        );
+        assert!(config.class_mappings.is_empty());
+    }
+
+    #[test]
+    fn lines_of_broken_and_unknown_sections_are_skipped() {
+        let (config, diagnostics) = parse(
+            "sections",
+            "[]\nfull = true\n[work]\nmulti = true\n[preset a]\ntop = 3\n",
+        );
+        let messages: Vec<_> = diagnostics.iter().map(|(line, _, m)| (*line, m)).collect();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0], (1, &String::from("Empty section header.")));
+        assert_eq!(messages[1].0, 3);
+        assert!(messages[1].1.starts_with("Unknown section work"));
+        assert_eq!((config.defaults.full, config.defaults.multi), (None, None));
+        assert_eq!(config.preset("a").unwrap().top, Some(3));
+    }
+
+    #[test]
+    fn presets_only_take_report_options() {
+        let (config, diagnostics) = parse(
+            "preset",
+            "[preset work]\nfull = true\nclass_alias = \"a\", \"b\"\nsource = other.conf\n",
+        );
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].0, 3);
+        assert!(
+            diagnostics[0]
+                .2
+                .starts_with("Only report options can be set in [preset work]")
+        );
+        assert_eq!(
+            diagnostics[1],
+            (4, 1, String::from("Files can't be sourced from a preset."))
+        );
+        assert_eq!(config.preset("work").unwrap().full, Some(true));
+        assert!(config.class_mappings.is_empty());
+    }
+
+    #[test]
+    fn checked_config_parses_back_to_the_same_config() {
+        let (mut config, diagnostics) = parse(
+            "check",
+            r##"
+class_alias = "Alacritty", "terminal"
+class_rewrite = "^org\.gnome\.(.*)$", "$1"
+title_rewrite = "firefox", "^\(\d+\) ", ""
+category = "say \"hi\"", "kitty", "title:*back\\slash*"
+ignore_class = "wofi"
+ignore_mode = "drop"
+max_span = "1h30m"
+min_span = "2s"
+palette = "red", "#123456", 200
+color = "kitty", "bright blue"
+glyphs = "ascii"
+full = true
+by = "category"
+min_percent = 2.5
+
+[preset week]
+days = 7
+class = "firefox"
+sort = "name"
+format = "markdown"
+"##,
+        );
+        assert!(diagnostics.is_empty());
+        config.add_builtin_rules();
+
+        // the check output includes the built in rules, so it's read back without them
+        let checked = config.to_string();
+        let (reparsed, diagnostics) = parse("checked", &checked);
+        assert!(diagnostics.is_empty());
+        assert_eq!(reparsed.to_string(), checked);
+    }
+
+    #[test]
+    fn percentages_are_finite_and_at_most_100() {
+        #[rustfmt::skip]
+        let cases = [
+            ("0", Some(0.0)),
+            ("2.5", Some(2.5)),
+            ("100", Some(100.0)),
+            ("100.1", None),
+            ("-1", None),
+            ("NaN", None),
+            ("inf", None),
+            ("5%", None),
+            ("", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_percent(value), expected, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn durations_need_a_unit_for_every_number() {
+        #[rustfmt::skip]
+        let cases = [
+            ("90s", Some(90_000)),
+            ("30m", Some(1_800_000)),
+            ("1h30m", Some(5_400_000)),
+            ("1h1m1s", Some(3_661_000)),
+            (" 2s ", Some(2_000)),
+            ("0s", Some(0)),
+            ("", None),
+            ("  ", None),
+            ("30", None),
+            ("1h30", None),
+            ("h", None),
+            ("1d", None),
+            ("-5m", None),
+            ("1.5h", None),
+            // too many milliseconds for a u64, in the number, the unit or the sum
+            ("99999999999999999999s", None),
+            ("5124095576030432h", None),
+            ("5124095576030431h5124095576030431h", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_duration(value), expected, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn class_rules_apply_in_order_and_the_first_match_wins() {
+        let (mut config, diagnostics) = parse(
+            "class-rules",
+            r#"
+class_alias = "Alacritty", "terminal"
+class_rewrite = "^gnome-(.*)$", "$1"
+class_rewrite = "^gnome-.*$", "gnome"
+class_rewrite = "^Alacritty$", "alacritty"
+class_rewrite = "^steam_app_1234$", "factorio"
+"#,
+        );
+        assert!(diagnostics.is_empty());
+        config.add_builtin_rules();
+
+        #[rustfmt::skip]
+        let cases = [
+            ("gnome-files", "files"),
+            // aliases are checked before the rewrite rules
+            ("Alacritty", "terminal"),
+            // user rules come before the default steam rule
+            ("steam_app_1234", "factorio"),
+            ("steam_app_42", "steam"),
+            ("gamescope-steam_app_42", "steam"),
+            ("steam", "steam"),
+            ("kitty", "kitty"),
+            ("SYSTEM", "SYSTEM"),
+        ];
+        for (class, expected) in cases {
+            assert_eq!(config.map_class(class), expected, "{class}");
+        }
+    }
+
+    #[test]
+    fn every_matching_title_rule_applies_in_order() {
+        let (config, diagnostics) = parse(
+            "title-rules",
+            r#"
+title_rewrite = "*", "^\(\d+\) ", ""
+title_rewrite = "firefox", " — Mozilla Firefox$", ""
+title_rewrite = "firefox", "^Inbox$", "Mail"
+title_rewrite = "kitty", "nvim", "vim"
+"#,
+        );
+        assert!(diagnostics.is_empty());
+
+        #[rustfmt::skip]
+        let cases = [
+            ("firefox", "(3) Inbox — Mozilla Firefox", "Mail"),
+            ("kitty", "(2) chat", "chat"),
+            // a rewritten title is trimmed
+            ("kitty", " nvim ", "vim"),
+            // one no rule changes is kept as logged
+            ("kitty", " spaced ", " spaced "),
+            ("foot", "Inbox — Mozilla Firefox", "Inbox — Mozilla Firefox"),
+            ("SYSTEM", "(1) idle", "(1) idle"),
+        ];
+        for (class, title, expected) in cases {
+            assert_eq!(config.map_title(class, title), expected, "{class}: {title}");
+        }
+    }
+
+    #[test]
+    fn classes_are_grouped_into_the_first_matching_category() {
+        let (config, diagnostics) = parse(
+            "categories",
+            r#"
+category = "dev", "kitty", "code", "title:*nvim*"
+category = "web", "firefox", "chrom*"
+category = "terminals", "kitty", "foot"
+"#,
+        );
+        assert!(diagnostics.is_empty());
+
+        #[rustfmt::skip]
+        let cases = [
+            ("kitty", "", "dev"),
+            ("chromium", "", "web"),
+            ("foot", "", "terminals"),
+            // title: patterns match the title of any class
+            ("foot", "nvim main.rs", "dev"),
+            ("mpv", "", UNCATEGORIZED),
+        ];
+        for (class, title, expected) in cases {
+            assert_eq!(config.category(class, title), expected, "{class}: {title}");
+        }
+
+        let mut settings = crate::Settings::new(config);
+        settings.group_by = GroupBy::Category;
+        let key = |settings: &crate::Settings, class: &str| {
+            crate::log_parsing::key(settings, &class.to_string(), &String::new())
+        };
+        assert_eq!(key(&settings, "firefox").as_deref(), Some("web"));
+        assert_eq!(key(&settings, "mpv").as_deref(), Some(UNCATEGORIZED));
+
+        // --category lists the classes of one category, and leaves out the rest
+        settings.category_arg = String::from("web");
+        assert_eq!(key(&settings, "firefox").as_deref(), Some("firefox"));
+        assert_eq!(key(&settings, "kitty"), None);
+    }
+
+    #[test]
+    fn windows_are_ignored_by_class_or_title() {
+        let (config, diagnostics) = parse(
+            "ignore",
+            r#"
+ignore_class = "wofi", "polkit-*"
+ignore_title = "Authentication Required*"
+ignore_mode = "drop"
+"#,
+        );
+        assert!(diagnostics.is_empty());
+        assert!(config.ignore_mode == IgnoreMode::Drop);
+
+        #[rustfmt::skip]
+        let cases = [
+            ("wofi", "", true),
+            ("polkit-gnome-agent", "", true),
+            ("kitty", "Authentication Required for sudo", true),
+            // class patterns don't match titles, and title patterns don't match classes
+            ("kitty", "wofi", false),
+            ("Authentication Required", "", false),
+            ("kitty", "", false),
+        ];
+        for (class, title, expected) in cases {
+            assert_eq!(
+                config.is_ignored(class, title),
+                expected,
+                "{class}: {title}"
+            );
+        }
+
+        // --exclude adds class patterns for one report
+        let mut settings = crate::Settings::new(config);
+        settings.excludes.push(Pattern::new("ki*"));
+        assert!(settings.is_ignored("kitty", ""));
+        assert!(settings.is_ignored("wofi", ""));
+        assert!(!settings.is_ignored("foot", "kitty"));
+    }
+
+    #[test]
+    fn glyph_lines_take_a_name_or_four_values() {
+        let (config, diagnostics) = parse(
+            "glyphs",
+            "glyphs = \"blocks\"\nglyphs = \"ab\", \"cd\"\nglyphs = \"ab\", \"cd\", \"ef\", \"gh\"\nglyphs = \"dots\"\n",
+        );
+        let lines: Vec<usize> = diagnostics.iter().map(|(line, ..)| *line).collect();
+        assert_eq!(lines, [2, 3, 4]);
+        assert_eq!(
+            diagnostics[1].2,
+            "The idle glyph should be a single character."
+        );
+        assert!(config.glyphs == Glyphs::named("blocks"));
     }
 }
@@ -0,0 +1,415 @@
+use directories::BaseDirs;
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::config::Pattern;
+
+/// One non-empty line of hyprlog.conf
+pub enum Line {
+    /// `[name args...]`
+    Section { name: Value, args: Vec<Value> },
+    /// `name = value, value, ...`
+    Directive { name: Value, values: Vec<Value> },
+}
+
+/// A word or value along with the column it starts at
+pub struct Value {
+    pub text: String,
+    pub column: usize,
+}
+
+/// What's wrong with a line and where, columns start at 1
+pub struct ParseError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(column: usize, message: impl Into<String>) -> Self {
+        Self {
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+/// A problem in the config, the line it's on is skipped
+pub struct Diagnostic {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(file: &Path, line: usize, error: ParseError) -> Self {
+        Self {
+            file: file.to_path_buf(),
+            line,
+            column: error.column,
+            message: error.message,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let file = display_path(&self.file);
+        match self.line {
+            // not about a specific line, like when the file can't be read
+            0 => write!(f, "{}: {}", file, self.message),
+            line => write!(f, "{}:{}:{}: {}", file, line, self.column, self.message),
+        }
+    }
+}
+
+/// The path with the home directory shortened to ~
+pub fn display_path(path: &Path) -> String {
+    let home = BaseDirs::new().map(|b| b.home_dir().to_path_buf());
+    match home
+        .as_deref()
+        .and_then(|home| path.strip_prefix(home).ok())
+    {
+        Some(relative) => format!("~/{}", relative.display()),
+        None => path.display().to_string(),
+    }
+}
+
+/// The files a `source` value refers to. `~` is expanded to the home directory, relative paths
+/// start at `base`, and any component can be a glob with `*` and `?`.
+pub fn expand_path(value: &str, base: &Path) -> Result<Vec<PathBuf>, String> {
+    let path = match value.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            let Some(dirs) = BaseDirs::new() else {
+                return Err(String::from("Can't expand ~ without a home directory."));
+            };
+            dirs.home_dir().join(rest.trim_start_matches('/'))
+        }
+        _ => base.join(value),
+    };
+
+    if !value.contains(['*', '?']) {
+        return match path.is_file() {
+            true => Ok(vec![path]),
+            false => Err(format!("{} doesn't exist.", display_path(&path))),
+        };
+    }
+
+    let mut matches = vec![PathBuf::new()];
+    for component in path.components() {
+        let component = component.as_os_str();
+        let text = component.to_string_lossy();
+        if !text.contains(['*', '?']) {
+            matches.iter_mut().for_each(|m| m.push(component));
+            continue;
+        }
+        let pattern = Pattern::new(&text);
+        let mut expanded = Vec::new();
+        for dir in &matches {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                // like shells, wildcards don't match hidden files
+                if pattern.matches(&name) && (!name.starts_with('.') || text.starts_with('.')) {
+                    expanded.push(entry.path());
+                }
+            }
+        }
+        expanded.sort();
+        matches = expanded;
+    }
+    matches.retain(|m| m.is_file());
+    match matches.is_empty() {
+        true => Err(format!("{} doesn't match any files.", display_path(&path))),
+        false => Ok(matches),
+    }
+}
+
+/// The name of this machine, for [host HOSTNAME] blocks
+pub fn hostname() -> String {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .or_else(|| env::var("HOSTNAME").ok())
+        .unwrap_or_default()
+}
+
+/// Splits a line into a section header or a directive and its values. Values are quoted and
+/// separated by ',', a backslash escapes a quote or another backslash. Simple values like
+/// numbers and durations may be left unquoted. '#' starts a comment outside of quotes.
+pub fn parse_line(line: &str) -> Result<Option<Line>, ParseError> {
+    let mut cursor = Cursor {
+        chars: line.chars().collect(),
+        index: 0,
+    };
+    cursor.skip_whitespace();
+    if cursor.at_end() {
+        return Ok(None);
+    }
+
+    if cursor.next_if('[') {
+        let mut words = Vec::new();
+        loop {
+            cursor.skip_whitespace();
+            if cursor.next_if(']') {
+                break;
+            }
+            let column = cursor.column();
+            let text = cursor.take_while(|c| !c.is_whitespace() && c != ']' && c != '#');
+            if text.is_empty() {
+                return Err(ParseError::new(column, "Missing ']'."));
+            }
+            words.push(Value { text, column });
+        }
+        cursor.expect_end("Unexpected text after ']'.")?;
+        if words.is_empty() {
+            return Err(ParseError::new(1, "Empty section header."));
+        }
+        let name = words.remove(0);
+        return Ok(Some(Line::Section { name, args: words }));
+    }
+
+    let column = cursor.column();
+    let text = cursor.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+    if text.is_empty() {
+        return Err(ParseError::new(column, "Expected a directive name."));
+    }
+    let name = Value { text, column };
+    cursor.skip_whitespace();
+    if !cursor.next_if('=') {
+        return Err(ParseError::new(
+            cursor.column(),
+            format!("Missing '=' after {}.", name.text),
+        ));
+    }
+
+    let mut values = Vec::new();
+    loop {
+        cursor.skip_whitespace();
+        let column = cursor.column();
+        let text = if cursor.next_if('"') {
+            let mut text = String::new();
+            loop {
+                match cursor.next() {
+                    Some('"') => break,
+                    Some('\\') if matches!(cursor.peek(), Some('"') | Some('\\')) => {
+                        text.push(cursor.next().unwrap());
+                    }
+                    Some(ch) => text.push(ch),
+                    None => return Err(ParseError::new(column, "Unterminated quote.")),
+                }
+            }
+            text
+        } else {
+            let text = cursor.take_while(|c| c != ',' && c != '#');
+            let text = text.trim_end().to_string();
+            if text.is_empty() {
+                return Err(ParseError::new(column, "Missing value."));
+            }
+            if let Some(offset) = text.chars().position(|c| c == '"') {
+                return Err(ParseError::new(
+                    column + offset,
+                    "Value is not properly quoted.",
+                ));
+            }
+            text
+        };
+        values.push(Value { text, column });
+
+        cursor.skip_whitespace();
+        if !cursor.next_if(',') {
+            cursor.expect_end("Values must be separated by ','.")?;
+            return Ok(Some(Line::Directive { name, values }));
+        }
+    }
+}
+
+struct Cursor {
+    chars: Vec<char>,
+    index: usize,
+}
+
+impl Cursor {
+    fn column(&self) -> usize {
+        self.index + 1
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.index += 1;
+        Some(ch)
+    }
+
+    fn next_if(&mut self, expected: char) -> bool {
+        let matched = self.peek() == Some(expected);
+        if matched {
+            self.index += 1;
+        }
+        matched
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut text = String::new();
+        while let Some(ch) = self.peek().filter(|c| predicate(*c)) {
+            text.push(ch);
+            self.index += 1;
+        }
+        text
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    // the rest of the line is a comment or nothing
+    fn at_end(&self) -> bool {
+        matches!(self.peek(), None | Some('#'))
+    }
+
+    fn expect_end(&mut self, message: &str) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        match self.at_end() {
+            true => Ok(()),
+            false => Err(ParseError::new(self.column(), message)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a fresh directory with the given empty files, removed by the caller
+    fn temp_dir(name: &str, files: &[&str]) -> PathBuf {
+        let files: Vec<(&str, &str)> = files.iter().map(|file| (*file, "")).collect();
+        crate::test_files::temp_dir(name, &files)
+    }
+
+    fn expand(value: &str, base: &Path) -> Result<Vec<String>, String> {
+        let paths = expand_path(value, base)?;
+        Ok(paths
+            .iter()
+            .map(|p| p.strip_prefix(base).unwrap().display().to_string())
+            .collect())
+    }
+
+    // the values of a directive line, with their columns
+    fn values(line: &str) -> Vec<(String, usize)> {
+        match parse_line(line) {
+            Ok(Some(Line::Directive { values, .. })) => {
+                values.into_iter().map(|v| (v.text, v.column)).collect()
+            }
+            _ => panic!("{line:?} is not a directive"),
+        }
+    }
+
+    fn error(line: &str) -> (usize, String) {
+        match parse_line(line) {
+            Err(e) => (e.column, e.message),
+            Ok(_) => panic!("{line:?} parsed without an error"),
+        }
+    }
+
+    #[test]
+    fn blank_and_comment_lines_are_skipped() {
+        for line in ["", "   ", "# comment", "  # indented comment"] {
+            assert!(matches!(parse_line(line), Ok(None)), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn directive_values_have_their_columns() {
+        let Ok(Some(Line::Directive { name, .. })) = parse_line("  category = a") else {
+            panic!("not a directive");
+        };
+        assert_eq!((name.text.as_str(), name.column), ("category", 3));
+        assert_eq!(
+            values(r#"name = "a, b", c  # comment"#),
+            [(String::from("a, b"), 8), (String::from("c"), 16)]
+        );
+    }
+
+    #[test]
+    fn quotes_and_backslashes_can_be_escaped() {
+        assert_eq!(values(r#"t = "say \"hi\" \\ ok""#)[0].0, r#"say "hi" \ ok"#);
+        // other backslashes are kept, regexes need them
+        assert_eq!(values(r#"t = "\d+ \(x\)""#)[0].0, r"\d+ \(x\)");
+        assert_eq!(values(r#"t = "a # b""#)[0].0, "a # b");
+    }
+
+    #[test]
+    fn section_headers_split_into_words() {
+        let Ok(Some(Line::Section { name, args })) = parse_line("[host  a b] # comment") else {
+            panic!("not a section");
+        };
+        assert_eq!((name.text.as_str(), name.column), ("host", 2));
+        let args: Vec<_> = args.iter().map(|a| (a.text.as_str(), a.column)).collect();
+        assert_eq!(args, [("a", 8), ("b", 10)]);
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        #[rustfmt::skip]
+        let cases = [
+            (r#"t = "abc"#, 5, "Unterminated quote."),
+            (r#"t = ab"c"#, 7, "Value is not properly quoted."),
+            (r#"t = a b "c""#, 9, "Value is not properly quoted."),
+            ("t = ", 5, "Missing value."),
+            ("t = a,", 7, "Missing value."),
+            (r#"t "a""#, 3, "Missing '=' after t."),
+            ("= a", 1, "Expected a directive name."),
+            (r#"t = "a" "b""#, 9, "Values must be separated by ','."),
+            ("[preset work", 13, "Missing ']'."),
+            ("[]", 1, "Empty section header."),
+            ("[host a] x", 10, "Unexpected text after ']'."),
+        ];
+        for (line, column, message) in cases {
+            assert_eq!(error(line), (column, String::from(message)), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn globs_match_sorted_files_but_not_hidden_ones() {
+        let dir = temp_dir(
+            "globs",
+            &[
+                "b.conf",
+                "a.conf",
+                ".hidden.conf",
+                "notes.txt",
+                "sub/c.conf",
+            ],
+        );
+        fs::create_dir_all(dir.join("dir.conf")).unwrap();
+
+        assert_eq!(expand("*.conf", &dir).unwrap(), ["a.conf", "b.conf"]);
+        assert_eq!(expand(".*.conf", &dir).unwrap(), [".hidden.conf"]);
+        assert_eq!(expand("?.conf", &dir).unwrap(), ["a.conf", "b.conf"]);
+        assert_eq!(expand("*/c.conf", &dir).unwrap(), ["sub/c.conf"]);
+        assert_eq!(expand("notes.txt", &dir).unwrap(), ["notes.txt"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_files_are_errors() {
+        let dir = temp_dir("missing", &["a.conf"]);
+        let error = expand("b.conf", &dir).unwrap_err();
+        assert!(error.ends_with("b.conf doesn't exist."), "{error}");
+        let error = expand("*.txt", &dir).unwrap_err();
+        assert!(error.ends_with("*.txt doesn't match any files."), "{error}");
+        // a directory isn't a config file
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        assert!(expand("sub", &dir).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
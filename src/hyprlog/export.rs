@@ -0,0 +1,418 @@
+use crate::log_parsing::{ParsedLog, ms_per_section, timeline};
+use crate::view::format_duration;
+use crate::{GroupBy, OutputFormat, Settings};
+use serde_json::{Value, json};
+use std::fmt::Write;
+use std::io;
+
+/// Prints the report in a format for scripts and documents instead of the terminal. The fields
+/// are documented in the README, new ones are only ever added. `groups` are the keys each row
+/// stands for, as returned by `collapse_tail`.
+pub fn print_report(
+    settings: &Settings,
+    rows: &[(String, u64)],
+    groups: &[Vec<String>],
+    total: u64,
+    log: &ParsedLog,
+) {
+    let buckets = buckets(settings, rows, groups, log);
+    let result = match settings.format {
+        OutputFormat::Json => {
+            println!("{:#}", json_report(settings, rows, total, log, buckets));
+            Ok(())
+        }
+        OutputFormat::Csv => write_separated(io::stdout(), settings, rows, total, log, b','),
+        OutputFormat::Tsv => write_separated(io::stdout(), settings, rows, total, log, b'\t'),
+        OutputFormat::Markdown => {
+            print!("{}", markdown(settings, rows, total, log, buckets));
+            Ok(())
+        }
+        OutputFormat::Terminal => unreachable!("the terminal report is drawn by view.rs"),
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to write report: {e}");
+    }
+}
+
+/// What the keys of the rows are
+pub fn grouped_by(settings: &Settings) -> &'static str {
+    if settings.full {
+        "window"
+    } else if !settings.class_arg.is_empty() {
+        "title"
+    } else if !settings.category_arg.is_empty() {
+        "class"
+    } else {
+        match settings.group_by {
+            GroupBy::Class => "class",
+            GroupBy::Category => "category",
+        }
+    }
+}
+
+/// `grouped_by` as the heading of the table's first column
+pub fn grouped_by_heading(settings: &Settings) -> String {
+    let grouped_by = grouped_by(settings);
+    grouped_by[..1].to_uppercase() + &grouped_by[1..]
+}
+
+/// The share of `total` in percent, rounded to two places, 0 when nothing was active
+pub fn percent(duration: u64, total: u64) -> f64 {
+    match total {
+        0 => 0.0,
+        total => (10_000.0 * duration as f64 / total as f64).round() / 100.0,
+    }
+}
+
+/// The start of a timeline section in ms, the key that was active the longest and how many ms
+/// anything was active
+type Bucket = (i64, String, i64);
+
+/// `--buckets N` splits the interval into N sections of the returned length, keys folded into
+/// the Other row are named after it
+fn buckets(
+    settings: &Settings,
+    rows: &[(String, u64)],
+    groups: &[Vec<String>],
+    log: &ParsedLog,
+) -> Option<(i64, Vec<Bucket>)> {
+    let count = settings.buckets?;
+    let ms_per_section = ms_per_section(&settings.interval, count);
+    let start = settings.interval.start.timestamp_millis();
+    let sections = timeline(&log.spans, count, &settings.interval, settings, None)
+        .into_iter()
+        .enumerate()
+        .map(|(i, (key, _, active, ..))| {
+            let key = match groups.iter().position(|keys| keys.contains(&key)) {
+                Some(index) => rows[index].0.clone(),
+                None => key,
+            };
+            (start + i as i64 * ms_per_section, key, active)
+        })
+        .collect();
+    Some((ms_per_section, sections))
+}
+
+fn timestamp(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+        .unwrap_or_else(|| ms.to_string())
+}
+
+fn json_report(
+    settings: &Settings,
+    rows: &[(String, u64)],
+    total: u64,
+    log: &ParsedLog,
+    buckets: Option<(i64, Vec<Bucket>)>,
+) -> Value {
+    let mut report = json!({
+        "interval": {
+            "start": timestamp(settings.interval.start.timestamp_millis()),
+            "end": timestamp(settings.interval.end.timestamp_millis()),
+        },
+        "grouped_by": grouped_by(settings),
+        "rows": rows
+            .iter()
+            .map(|(key, duration)| json!({
+                "key": key,
+                "duration_ms": duration,
+                "percent": percent(*duration, total),
+            }))
+            .collect::<Vec<_>>(),
+        "total_ms": total,
+        "unaccounted_ms": log.unaccounted(settings),
+        "idle_inhibited_ms": log.inhibited_idle,
+    });
+    if let Some((section_ms, sections)) = buckets {
+        report["timeline"] = json!({
+            "section_ms": section_ms,
+            "sections": sections
+                .iter()
+                .map(|(start, key, active)| json!({
+                    "start": timestamp(*start),
+                    "key": (!key.is_empty()).then_some(key),
+                    "active_ms": active,
+                }))
+                .collect::<Vec<_>>(),
+        });
+    }
+    if settings.strict {
+        report["anomalies"] = log
+            .anomalies
+            .iter()
+            .map(|anomaly| {
+                json!({
+                    "time": timestamp(anomaly.timestamp),
+                    "description": anomaly.description,
+                })
+            })
+            .collect();
+    }
+    report
+}
+
+// one record per row. The totals and the interval are the same on every line, so each line can be
+// checked on its own.
+fn write_separated(
+    out: impl io::Write,
+    settings: &Settings,
+    rows: &[(String, u64)],
+    total: u64,
+    log: &ParsedLog,
+    delimiter: u8,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(out);
+    writer.write_record([
+        grouped_by(settings),
+        "duration_ms",
+        "percent",
+        "total_ms",
+        "unaccounted_ms",
+        "idle_inhibited_ms",
+        "interval_start",
+        "interval_end",
+    ])?;
+    let totals = [
+        total.to_string(),
+        log.unaccounted(settings).to_string(),
+        log.inhibited_idle.to_string(),
+        timestamp(settings.interval.start.timestamp_millis()),
+        timestamp(settings.interval.end.timestamp_millis()),
+    ];
+    for (key, duration) in rows {
+        writer.write_record(
+            [
+                key.clone(),
+                duration.to_string(),
+                format!("{:.2}", percent(*duration, total)),
+            ]
+            .iter()
+            .chain(&totals),
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn markdown(
+    settings: &Settings,
+    rows: &[(String, u64)],
+    total: u64,
+    log: &ParsedLog,
+    buckets: Option<(i64, Vec<Bucket>)>,
+) -> String {
+    // a | would end the table cell
+    let escape = |key: &str| key.replace('|', "\\|");
+    let heading = grouped_by_heading(settings);
+
+    let mut markdown = String::new();
+    writeln!(markdown, "## {}\n", settings.interval.date_str()).unwrap();
+    writeln!(markdown, "| {heading} | Duration | Percent |").unwrap();
+    writeln!(markdown, "| --- | ---: | ---: |").unwrap();
+    for (key, duration) in rows {
+        writeln!(
+            markdown,
+            "| {} | {} | {:.2}% |",
+            escape(key),
+            format_duration(*duration),
+            percent(*duration, total)
+        )
+        .unwrap();
+    }
+    // like the terminal table, the total only covers the rows shown, e.g. one class with -c
+    let rows_total: u64 = rows.iter().map(|(_, duration)| duration).sum();
+    writeln!(
+        markdown,
+        "| **Total** | **{}** | **{:.2}%** |",
+        format_duration(rows_total),
+        percent(rows_total, total)
+    )
+    .unwrap();
+    let unaccounted = log.unaccounted(settings);
+    if unaccounted > 0 {
+        writeln!(
+            markdown,
+            "| *Unaccounted* | *{}* | |",
+            format_duration(unaccounted)
+        )
+        .unwrap();
+    }
+    if settings.show_inhibited {
+        writeln!(
+            markdown,
+            "| *Idle inhibited* | *{}* | |",
+            format_duration(log.inhibited_idle)
+        )
+        .unwrap();
+    }
+    if let Some((section_ms, sections)) = buckets {
+        writeln!(markdown, "\n### Timeline\n").unwrap();
+        writeln!(markdown, "| Start | End | {heading} | Active |").unwrap();
+        writeln!(markdown, "| --- | --- | --- | ---: |").unwrap();
+        for (start, key, active) in sections {
+            writeln!(
+                markdown,
+                "| {} | {} | {} | {} |",
+                timestamp(start),
+                timestamp(start + section_ms),
+                escape(&key),
+                format_duration(active as u64)
+            )
+            .unwrap();
+        }
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::interval::Interval;
+    use crate::log_parsing::Span;
+
+    fn rows(rows: &[(&str, u64)]) -> Vec<(String, u64)> {
+        rows.iter().map(|(key, d)| (key.to_string(), *d)).collect()
+    }
+
+    #[test]
+    fn percent_is_rounded_to_two_places() {
+        assert_eq!(percent(1, 3), 33.33);
+        assert_eq!(percent(2, 3), 66.67);
+        assert_eq!(percent(5, 5), 100.0);
+    }
+
+    #[test]
+    fn percent_of_nothing_is_zero() {
+        assert_eq!(percent(0, 0), 0.0);
+    }
+
+    // the first minute of 1970, so the interval columns don't depend on today
+    fn first_minute() -> Settings {
+        let mut settings = Settings::new(Config::without_file());
+        settings.interval = Interval {
+            start: chrono::DateTime::from_timestamp_millis(0).unwrap(),
+            end: chrono::DateTime::from_timestamp_millis(60_000).unwrap(),
+        };
+        settings
+    }
+
+    #[test]
+    fn csv_quotes_keys_with_commas_and_quotes() {
+        let settings = first_minute();
+        let rows = rows(&[("a, b", 1), ("say \"hi\"", 1)]);
+        let mut out = Vec::new();
+        write_separated(&mut out, &settings, &rows, 2, &ParsedLog::default(), b',').unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "class,duration_ms,percent,total_ms,unaccounted_ms,idle_inhibited_ms,interval_start,interval_end",
+                "\"a, b\",1,50.00,2,0,0,1970-01-01T00:00:00Z,1970-01-01T00:01:00Z",
+                "\"say \"\"hi\"\"\",1,50.00,2,0,0,1970-01-01T00:00:00Z,1970-01-01T00:01:00Z",
+            ]
+        );
+    }
+
+    #[test]
+    fn tsv_keeps_commas() {
+        let settings = first_minute();
+        let rows = rows(&[("a, b", 1)]);
+        let log = ParsedLog {
+            inhibited_idle: 5,
+            ..ParsedLog::default()
+        };
+        let mut out = Vec::new();
+        write_separated(&mut out, &settings, &rows, 1, &log, b'\t').unwrap();
+        let tsv = String::from_utf8(out).unwrap();
+        assert_eq!(
+            tsv.lines().nth(1).unwrap(),
+            "a, b\t1\t100.00\t1\t0\t5\t1970-01-01T00:00:00Z\t1970-01-01T00:01:00Z"
+        );
+    }
+
+    #[test]
+    fn markdown_escapes_pipes_in_keys() {
+        let settings = Settings::new(Config::without_file());
+        let rows = rows(&[("a | b", 60_000)]);
+        let markdown = markdown(&settings, &rows, 60_000, &ParsedLog::default(), None);
+        let row = markdown.lines().nth(4).unwrap();
+        assert!(row.starts_with("| a \\| b | "), "{row}");
+    }
+
+    #[test]
+    fn markdown_total_only_counts_the_rows() {
+        let mut settings = Settings::new(Config::without_file());
+        settings.class_arg = String::from("firefox");
+        let rows = rows(&[("a", 30_000), ("b", 15_000)]);
+        let markdown = markdown(&settings, &rows, 180_000, &ParsedLog::default(), None);
+        let total = markdown.lines().nth(6).unwrap();
+        assert!(total.starts_with("| **Total** | "), "{total}");
+        assert!(total.ends_with(" | **25.00%** |"), "{total}");
+    }
+
+    #[test]
+    fn buckets_name_folded_keys_after_the_other_row() {
+        let mut settings = Settings::new(Config::without_file());
+        settings.multi_timeline = true;
+        settings.buckets = Some(3);
+        settings.interval = Interval {
+            start: chrono::DateTime::from_timestamp_millis(0).unwrap(),
+            end: chrono::DateTime::from_timestamp_millis(3_000).unwrap(),
+        };
+        let span = |class: &str, start, end| Span {
+            start,
+            end,
+            class: class.to_string(),
+            title: String::new(),
+        };
+        let log = ParsedLog {
+            spans: vec![
+                span("a", 0, 1_000),
+                span("b", 1_000, 2_000),
+                span("c", 2_000, 3_000),
+            ],
+            ..ParsedLog::default()
+        };
+        let rows = rows(&[("a", 1_000), ("Other (2)", 2_000)]);
+        let groups = [
+            vec![String::from("a")],
+            vec![String::from("b"), String::from("c")],
+        ];
+
+        let (_, sections) = buckets(&settings, &rows, &groups, &log).unwrap();
+        let keys: Vec<_> = sections.iter().map(|(_, key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["a", "Other (2)", "Other (2)"]);
+    }
+
+    #[test]
+    fn markdown_has_a_timeline_table_with_buckets() {
+        let settings = first_minute();
+        let rows = rows(&[("a", 60_000)]);
+        let sections = vec![(0, String::from("a"), 30_000), (30_000, String::new(), 0)];
+        let markdown = markdown(
+            &settings,
+            &rows,
+            60_000,
+            &ParsedLog::default(),
+            Some((30_000, sections)),
+        );
+        let timeline: Vec<_> = markdown
+            .lines()
+            .skip_while(|line| *line != "### Timeline")
+            .collect();
+        assert_eq!(
+            timeline[2..],
+            [
+                "| Start | End | Class | Active |",
+                "| --- | --- | --- | ---: |",
+                "| 1970-01-01T00:00:00Z | 1970-01-01T00:00:30Z | a | 00:30 |",
+                "| 1970-01-01T00:00:30Z | 1970-01-01T00:01:00Z |  | 0ms |",
+            ]
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use std::env;
+
+pub const GLYPH_SETS: &[&str] = &["fancy", "blocks", "ascii"];
+
+/// The characters a timeline is drawn with. Each ramp goes from the emptiest to the fullest
+/// section, a section with activity `f` between 0 and 1 uses glyph `ceil(f * len) - 1`.
+#[derive(Clone, PartialEq)]
+pub struct Glyphs {
+    /// Activity near the left side of a section
+    pub left: Vec<char>,
+    /// Activity near the right side of a section
+    pub right: Vec<char>,
+    /// Activity in the middle, or the fallback when the side isn't known
+    pub center: Vec<char>,
+    /// Sections without activity between two spans
+    pub idle: char,
+    /// Draw the timeline struck through, this turns idle spaces into a thin line
+    pub strike: bool,
+}
+
+impl Glyphs {
+    pub fn named(name: &str) -> Option<Self> {
+        let (left, right, center, idle, strike) = match name {
+            // the right ramp uses Symbols for Legacy Computing, which not every font has
+            "fancy" => ("▏▎▍▌▋▊██", "🮇🮈▐🮉🮊🮋██", "│┃█", ' ', true),
+            // block elements from the Linux console font
+            "blocks" => ("░▒▓█", "░▒▓█", "░▒▓█", '─', false),
+            "ascii" => (".:+#", ".:+#", ".:+#", '-', false),
+            _ => return None,
+        };
+        Some(Self {
+            left: left.chars().collect(),
+            right: right.chars().collect(),
+            center: center.chars().collect(),
+            idle,
+            strike,
+        })
+    }
+
+    /// A custom set from the `glyphs = "LEFT", "RIGHT", "CENTER", "IDLE"` config line
+    pub fn custom(left: &str, right: &str, center: &str, idle: &str) -> Result<Self, String> {
+        let mut idle_chars = idle.chars();
+        let (Some(idle), None) = (idle_chars.next(), idle_chars.next()) else {
+            return Err(String::from("The idle glyph should be a single character."));
+        };
+        if [left, right, center].iter().any(|ramp| ramp.is_empty()) {
+            return Err(String::from(
+                "Every glyph ramp needs at least one character.",
+            ));
+        }
+        Ok(Self {
+            left: left.chars().collect(),
+            right: right.chars().collect(),
+            center: center.chars().collect(),
+            idle,
+            strike: false,
+        })
+    }
+
+    /// The fancy set, unless the terminal probably can't show it. The Linux console only has
+    /// a small font, and without a UTF-8 locale only ASCII is safe.
+    pub fn detect() -> Self {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|var| env::var(var).ok().filter(|value| !value.is_empty()))
+            .unwrap_or_default();
+        Self::for_terminal(&locale, &env::var("TERM").unwrap_or_default())
+    }
+
+    // the set for a locale like "en_US.UTF-8" and a TERM like "linux"
+    fn for_terminal(locale: &str, term: &str) -> Self {
+        let locale = locale.to_lowercase();
+        let name = if !locale.contains("utf-8") && !locale.contains("utf8") {
+            "ascii"
+        } else if term == "linux" {
+            "blocks"
+        } else {
+            "fancy"
+        };
+        Self::named(name).unwrap()
+    }
+
+    /// The values of the `glyphs` config line that gives this set
+    pub fn setting(&self) -> Vec<String> {
+        if let Some(name) = GLYPH_SETS
+            .iter()
+            .find(|name| Self::named(name).as_ref() == Some(self))
+        {
+            return vec![name.to_string()];
+        }
+        [&self.left, &self.right, &self.center, &vec![self.idle]]
+            .iter()
+            .map(|ramp| ramp.iter().collect())
+            .collect()
+    }
+
+    /// Whether every glyph is plain ASCII, the rest of the report should then stick to it too
+    pub fn is_ascii(&self) -> bool {
+        [&self.left, &self.right, &self.center]
+            .iter()
+            .flat_map(|ramp| ramp.iter())
+            .chain([&self.idle])
+            .all(char::is_ascii)
+    }
+
+    pub fn choose(&self, fullness: f64, left: bool, right: bool) -> char {
+        if left && right {
+            // there is activity near both the left and right side of a section
+            return *self.center.last().unwrap();
+        }
+        let ramp = match (left, right) {
+            (true, false) => &self.left,
+            (false, true) => &self.right,
+            _ => &self.center,
+        };
+        if fullness.is_nan() || fullness <= 0.0 {
+            return ' ';
+        }
+        let index = (fullness * ramp.len() as f64).ceil() as usize;
+        ramp[index.clamp(1, ramp.len()) - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_sets_round_trip_through_their_setting() {
+        for name in GLYPH_SETS {
+            let glyphs = Glyphs::named(name).unwrap();
+            assert_eq!(glyphs.setting(), [*name]);
+            assert_eq!(glyphs.is_ascii(), *name == "ascii", "{name}");
+        }
+        assert!(Glyphs::named("Fancy").is_none());
+        assert!(Glyphs::named("").is_none());
+    }
+
+    #[test]
+    fn custom_sets_need_ramps_and_a_single_idle_glyph() {
+        #[rustfmt::skip]
+        let cases = [
+            (["abc", "d", "ef", "-"], true),
+            (["▏▎", "▕", "█", "─"], true),
+            (["abc", "abc", "abc", ""], false),
+            (["abc", "abc", "abc", "--"], false),
+            (["", "abc", "abc", "-"], false),
+            (["abc", "", "abc", "-"], false),
+            (["abc", "abc", "", "-"], false),
+        ];
+        for ([left, right, center, idle], ok) in cases {
+            let glyphs = Glyphs::custom(left, right, center, idle);
+            assert_eq!(glyphs.is_ok(), ok, "{left:?} {right:?} {center:?} {idle:?}");
+        }
+
+        // a custom set is written back as its four values, unless it is a named one
+        let glyphs = Glyphs::custom("abc", "d", "ef", "-").unwrap();
+        assert_eq!(glyphs.setting(), ["abc", "d", "ef", "-"]);
+        let ascii = Glyphs::custom(".:+#", ".:+#", ".:+#", "-").unwrap();
+        assert_eq!(ascii.setting(), ["ascii"]);
+    }
+
+    #[test]
+    fn ascii_is_used_without_a_utf8_locale() {
+        #[rustfmt::skip]
+        let cases = [
+            ("en_US.UTF-8", "xterm-kitty", "fancy"),
+            ("de_DE.utf8", "foot", "fancy"),
+            ("en_US.UTF-8", "linux", "blocks"),
+            ("C", "xterm-kitty", "ascii"),
+            ("POSIX", "linux", "ascii"),
+            ("en_US.ISO-8859-1", "xterm", "ascii"),
+            ("", "xterm-kitty", "ascii"),
+        ];
+        for (locale, term, expected) in cases {
+            let glyphs = Glyphs::for_terminal(locale, term);
+            assert_eq!(glyphs.setting(), [expected], "{locale:?} {term:?}");
+        }
+    }
+
+    #[test]
+    fn choose_picks_from_the_ramp_by_fullness() {
+        let glyphs = Glyphs::named("ascii").unwrap();
+        #[rustfmt::skip]
+        let cases = [
+            (0.0, false, false, ' '),
+            (f64::NAN, false, false, ' '),
+            (0.1, false, false, '.'),
+            (0.25, false, false, '.'),
+            (0.26, false, false, ':'),
+            (1.0, false, false, '#'),
+            (1.5, false, false, '#'),
+            (0.1, true, true, '#'),
+        ];
+        for (fullness, left, right, expected) in cases {
+            assert_eq!(glyphs.choose(fullness, left, right), expected, "{fullness}");
+        }
+    }
+}
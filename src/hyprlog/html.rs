@@ -0,0 +1,271 @@
+use crate::Settings;
+use crate::export::{grouped_by_heading, percent};
+use crate::log_parsing::{ParsedLog, Span, compute_durations, parse_log};
+use crate::log_reader::LogReader;
+use crate::svg::{self, BACKGROUND, DIMMED, SvgOptions, escape};
+use crate::view::{Legend, anomaly_time, collapse_tail, format_duration};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+const DAY_TIMELINE: SvgOptions = SvgOptions {
+    width: 520,
+    height: 36,
+    labels: true,
+};
+
+/// Writes the report as a single HTML file without any external assets, so it can be shared as is
+pub fn write_report(settings: &Settings, path: &Path) {
+    let mut reader = LogReader::new(settings);
+    let log = match parse_log(&mut reader, settings) {
+        Ok(log) => log,
+        Err(e) => {
+            eprintln!("Failed to parse log: {e:?}");
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = fs::write(path, report(settings, &log)) {
+        eprintln!("Failed to write {}: {e}", path.display());
+        std::process::exit(1);
+    }
+}
+
+fn report(settings: &Settings, log: &ParsedLog) -> String {
+    let (durations, total) = compute_durations(&log.spans, settings);
+    let (durations, groups) = collapse_tail(durations, total, settings);
+    let legend = Legend::new(&durations, &groups, settings);
+    let date_str = escape(&settings.interval.date_str());
+
+    let mut html = String::new();
+    writeln!(
+        &mut html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+        <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+        <title>hyprlog {date_str}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>{date_str}</h1>",
+        style()
+    )
+    .unwrap();
+
+    if durations.is_empty() {
+        html.push_str("<p class=\"dimmed\">Empty log.</p>\n</body>\n</html>\n");
+        return html;
+    }
+
+    html.push_str("<h2>Timeline</h2>\n");
+    if settings.multi_timeline {
+        for ((row, _), keys) in durations.iter().zip(&groups) {
+            // only the keys of this row are drawn
+            let colors: HashMap<String, _> = keys
+                .iter()
+                .filter_map(|key| Some((key.clone(), *legend.colors.get(key)?)))
+                .collect();
+            writeln!(&mut html, "<h3>{}</h3>", escape(row)).unwrap();
+            html.push_str(&svg::timeline(
+                &log.spans,
+                &settings.interval,
+                settings,
+                &colors,
+                &SvgOptions::default(),
+            ));
+        }
+    } else {
+        html.push_str(&svg::timeline(
+            &log.spans,
+            &settings.interval,
+            settings,
+            &legend.colors,
+            &SvgOptions::default(),
+        ));
+    }
+
+    let heading = grouped_by_heading(settings);
+    writeln!(
+        &mut html,
+        "<h2>Durations</h2>\n<table>\n<tr><th>{heading}</th><th class=\"number\">Duration</th><th class=\"number\">Percent</th></tr>"
+    )
+    .unwrap();
+    for (key, duration) in &durations {
+        writeln!(
+            &mut html,
+            "<tr><td>{}{}</td><td class=\"number\">{}</td><td class=\"number\">{:.2}%</td></tr>",
+            swatch(&legend, key),
+            escape(key),
+            format_duration(*duration),
+            percent(*duration, total)
+        )
+        .unwrap();
+    }
+    // only the rows shown, with --class that is the time of one class
+    let rows_total = durations.iter().map(|(_, duration)| duration).sum();
+    writeln!(
+        &mut html,
+        "<tr class=\"total\"><td>Total</td><td class=\"number\">{}</td><td class=\"number\">{:.2}%</td></tr>",
+        format_duration(rows_total),
+        percent(rows_total, total)
+    )
+    .unwrap();
+    let mut footer = Vec::new();
+    let unaccounted = log.unaccounted(settings);
+    if unaccounted > 0 {
+        footer.push(("Unaccounted", unaccounted));
+    }
+    if settings.show_inhibited {
+        footer.push(("Idle inhibited", log.inhibited_idle));
+    }
+    for (name, duration) in footer {
+        writeln!(
+            &mut html,
+            "<tr class=\"dimmed\"><td>{name}</td><td class=\"number\">{}</td><td></td></tr>",
+            format_duration(duration)
+        )
+        .unwrap();
+    }
+    html.push_str("</table>\n");
+
+    let days = settings.interval.days();
+    if days.len() > 1 {
+        html.push_str(
+            "<h2>Days</h2>\n<table>\n<tr><th>Day</th><th></th><th class=\"number\">Active</th><th>Longest</th></tr>\n",
+        );
+        for day in &days {
+            let start = day.start.timestamp_millis();
+            let end = day.end.timestamp_millis();
+            let spans: Vec<Span> = log
+                .spans
+                .iter()
+                .filter(|span| span.start < end && span.end > start)
+                .map(|span| Span {
+                    start: span.start.max(start),
+                    end: span.end.min(end),
+                    ..span.clone()
+                })
+                .collect();
+            let (day_durations, _) = compute_durations(&spans, settings);
+            let active: u64 = day_durations.iter().map(|(_, duration)| duration).sum();
+            // added up per row of the report, so folded keys count towards the Other row
+            let mut row_durations = vec![0; durations.len()];
+            for (key, duration) in &day_durations {
+                if let Some(index) = groups.iter().position(|keys| keys.contains(key)) {
+                    row_durations[index] += duration;
+                }
+            }
+            let longest = durations
+                .iter()
+                .zip(row_durations)
+                .filter(|(_, duration)| *duration > 0)
+                .max_by_key(|(_, duration)| *duration);
+            let longest = match longest {
+                Some(((key, _), duration)) => format!(
+                    "{}{} <span class=\"dimmed\">{}</span>",
+                    swatch(&legend, key),
+                    escape(key),
+                    format_duration(duration)
+                ),
+                None => String::new(),
+            };
+            writeln!(
+                &mut html,
+                "<tr><td>{}</td><td>{}</td><td class=\"number\">{}</td><td>{longest}</td></tr>",
+                escape(&day.day_label()),
+                svg::timeline(&spans, day, settings, &legend.colors, &DAY_TIMELINE).trim_end(),
+                format_duration(active)
+            )
+            .unwrap();
+        }
+        html.push_str("</table>\n");
+    }
+
+    if settings.strict && !log.anomalies.is_empty() {
+        html.push_str("<h2>Anomalies</h2>\n<table>\n");
+        for anomaly in &log.anomalies {
+            writeln!(
+                &mut html,
+                "<tr><td class=\"dimmed\">{}</td><td>{}</td></tr>",
+                anomaly_time(anomaly),
+                escape(anomaly.description)
+            )
+            .unwrap();
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+// the row's colour in front of its name, the table's legend for the timeline
+fn swatch(legend: &Legend, key: &str) -> String {
+    match legend.colors.get(key) {
+        Some(color) => format!(
+            "<span class=\"swatch\" style=\"background: {}\"></span>",
+            svg::css_color(*color)
+        ),
+        None => String::new(),
+    }
+}
+
+fn style() -> String {
+    format!(
+        "
+body {{ background: {BACKGROUND}; color: #e4e4e7; font-family: sans-serif; max-width: 980px; margin: 2em auto; padding: 0 1em; }}
+h1 {{ text-align: center; font-weight: normal; }}
+h2, h3 {{ font-weight: normal; color: {DIMMED}; }}
+svg {{ display: block; max-width: 100%; height: auto; }}
+table {{ border-collapse: collapse; }}
+th, td {{ padding: 0.2em 0.8em; text-align: left; vertical-align: middle; }}
+th {{ font-weight: normal; color: {DIMMED}; }}
+.number {{ text-align: right; font-variant-numeric: tabular-nums; white-space: nowrap; }}
+.total td {{ font-weight: bold; border-top: 1px solid #3f3f46; }}
+.dimmed {{ color: {DIMMED}; }}
+.swatch {{ display: inline-block; width: 0.8em; height: 0.8em; margin-right: 0.5em; border-radius: 2px; }}
+"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::interval::Interval;
+
+    const HOUR: i64 = 3_600_000;
+
+    #[test]
+    fn report_escapes_keys_and_totals_the_rows_and_days() {
+        let mut settings = Settings::new(Config::without_file());
+        settings.interval = Interval::from_day_count(2);
+        let days = settings.interval.days();
+        let span = |class: &str, day: &Interval, hours: i64| {
+            let start = day.start.timestamp_millis() + HOUR;
+            Span {
+                start,
+                end: start + hours * HOUR,
+                class: class.to_string(),
+                title: String::new(),
+            }
+        };
+        let log = ParsedLog {
+            spans: vec![span("<b>&co", &days[0], 2), span("kitty", &days[1], 1)],
+            ..ParsedLog::default()
+        };
+
+        let html = report(&settings, &log);
+        assert!(html.contains("&lt;b&gt;&amp;co"));
+        assert!(!html.contains("<b>&co"));
+        assert!(html.contains(
+            "<tr class=\"total\"><td>Total</td><td class=\"number\">03:00:00</td><td class=\"number\">100.00%</td></tr>"
+        ));
+
+        let days_table = &html[html.find("<h2>Days</h2>").unwrap()..];
+        for (day, longest) in days.iter().zip(["&lt;b&gt;&amp;co", "kitty"]) {
+            // the rows span several lines, the day's timeline is an SVG
+            let row = days_table
+                .split("<tr>")
+                .find(|row| row.starts_with(&format!("<td>{}</td>", day.day_label())))
+                .unwrap();
+            let longest_cell = &row[row.rfind("<td>").unwrap()..];
+            assert!(longest_cell.contains(longest), "{longest_cell}");
+        }
+    }
+}
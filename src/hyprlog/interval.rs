@@ -54,6 +54,47 @@ impl Interval {
         }
     }
 
+    /// Split the interval into one interval per local day, oldest first
+    pub fn days(&self) -> Vec<Interval> {
+        let mut days = Vec::new();
+        let mut day = self.start.with_timezone(&Local).date_naive();
+        loop {
+            let start = local_midnight_to_utc(day);
+            if start >= self.end {
+                break;
+            }
+            let next = day + TimeDelta::days(1);
+            days.push(Interval {
+                start,
+                end: local_midnight_to_utc(next),
+            });
+            day = next;
+        }
+        days
+    }
+
+    pub fn day_label(&self) -> String {
+        self.start
+            .with_timezone(&Local)
+            .format("%a %Y-%m-%d")
+            .to_string()
+    }
+
+    /// Every `step` hours of local wall-clock time on the day the interval starts, hours that a DST
+    /// change skips are left out
+    pub fn hours(&self, step: usize) -> Vec<(DateTime<Utc>, u32)> {
+        let day = self.start.with_timezone(&Local).date_naive();
+        (0..24)
+            .step_by(step)
+            .filter_map(|hour| {
+                let time = Local
+                    .from_local_datetime(&day.and_hms_opt(hour, 0, 0)?)
+                    .earliest()?;
+                Some((time.with_timezone(&Utc), hour))
+            })
+            .collect()
+    }
+
     pub fn contains_utc_timestamp_millis(&self, timestamp_ms: u64) -> bool {
         let secs = (timestamp_ms / 1_000) as i64;
         let nanos = ((timestamp_ms % 1_000) * 1_000_000) as u32;
@@ -1,263 +1,757 @@
 use std::{collections::HashMap, error::Error};
 
-use crate::{log_reader::LogReader, Settings};
+use crate::{
+    GroupBy, Settings, SortOrder, config::IgnoreMode, interval::Interval, log_reader::LogReader,
+};
 
-pub fn compute_durations(
-    reader: &mut LogReader,
-    settings: &Settings,
-) -> Result<(Vec<(String, u64)>, u64), Box<dyn Error>> {
-    let mut map: HashMap<String, u64> = HashMap::new();
+/// Per-key durations in the order picked with --sort, and the total active time
+pub type Durations = (Vec<(String, u64)>, u64);
 
-    let mut total = 0;
-    let mut last_timestamp = None;
-    let mut last_class: Option<String> = None;
-    let mut last_title: Option<String> = None;
+/// (dominant key, dominant key weight, filled ms, activity at left edge, activity at right edge)
+pub type Section = (String, i64, i64, bool, bool);
 
-    let _ = reader.reset();
-    for row in reader {
-        let record = row?;
-        let timestamp: u64 = (record[0].parse::<i64>()?) as u64;
-        let mut class = record[1].to_string();
-        let title = record[2].to_string();
-
-        // filter classes using hashmap to rename them according to config
-        class = match settings.config.class_mappings.get(&class) {
-            Some(filtered_class) => filtered_class.clone(),
-            None => class,
-        };
+/// A stretch of time where one window had focus, class and title are already mapped by the config
+#[derive(Debug, Clone)]
+pub struct Span {
+    pub start: i64,
+    pub end: i64,
+    pub class: String,
+    pub title: String,
+}
+
+/// The spans of a log, how much idle time was discounted by idle inhibiting classes, the parts
+/// of spans cut off by max_span, and the rows that didn't fit the idle state machine
+#[derive(Debug, Clone, Default)]
+pub struct ParsedLog {
+    pub spans: Vec<Span>,
+    pub inhibited_idle: u64,
+    pub cut: Vec<Span>,
+    pub anomalies: Vec<Anomaly>,
+}
+
+impl ParsedLog {
+    /// The time max_span cut off the spans that are part of the report, with `-c` only that of
+    /// the one class
+    pub fn unaccounted(&self, settings: &Settings) -> u64 {
+        self.cut
+            .iter()
+            .filter(|span| key(settings, &span.class, &span.title).is_some())
+            .map(|span| (span.end - span.start) as u64)
+            .sum()
+    }
+}
+
+/// A row that doesn't make sense in the state the log was in
+#[derive(Debug, Clone)]
+pub struct Anomaly {
+    pub timestamp: i64,
+    pub description: &'static str,
+}
+
+/// What the log says the user was doing after the last row.
+///
+/// | row            | Off              | Active                      | Idle                         |
+/// |----------------|------------------|-----------------------------|------------------------------|
+/// | boot           | Off              | Off, open span is discarded | Off                          |
+/// | shutdown       | Off              | close span, Off             | Off                          |
+/// | idle           | Idle             | close span, Idle            | Idle, duplicate idle         |
+/// | resume         | Off, anomaly     | Active, anomaly             | Active, with the idle window |
+/// | focus          | Active           | close span, Active          | Active (implicit resume)     |
+/// | idle class     | Idle, no window  | close span, Idle, no window | Idle, the window is dropped  |
+///
+/// A resume without an idle window goes back to Off, so after a locker was focused nothing is
+/// credited until the next focus row.
+///
+/// An implicit resume after an idle row is a guess, in strict mode the focused window is
+/// remembered but nothing is credited until the resume row arrives.
+enum State {
+    /// after boot or shutdown, until something is focused
+    Off,
+    Active {
+        since: i64,
+        class: String,
+        title: String,
+    },
+    /// `window` was focused when idle started, a resume goes back to it
+    Idle {
+        window: Option<(String, String)>,
+        cause: IdleCause,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum IdleCause {
+    /// a SYSTEM idle row, only a resume row ends it for sure
+    Event,
+    /// an idle class or ignored window was focused, the next focus row ends it
+    Focus,
+}
+
+/// Turns log rows into spans. This is the one place that interprets SYSTEM rows, idle classes
+/// and the ignore rules, so the table and the timeline always agree about what happened.
+pub struct SpanParser {
+    spans: Vec<Span>,
+    state: State,
+    anomalies: Vec<Anomaly>,
+    // whether the focused window matches an idle_inhibit_class
+    inhibits_idle: bool,
+    // when an idle row was ignored because of an idle inhibiting window
+    inhibited_since: Option<i64>,
+    inhibited_idle: u64,
+}
 
-        if class.contains("steam_app") {
-            class = String::from("steam");
+impl Default for SpanParser {
+    fn default() -> Self {
+        Self {
+            spans: Vec::new(),
+            state: State::Off,
+            anomalies: Vec::new(),
+            inhibits_idle: false,
+            inhibited_since: None,
+            inhibited_idle: 0,
         }
+    }
+}
 
+impl SpanParser {
+    pub fn push(&mut self, settings: &Settings, timestamp: i64, class: &str, title: &str) {
         if class == "SYSTEM" {
-            match title.as_str() {
-                "boot" => {
-                    last_timestamp = None;
-                    last_class = None;
-                    last_title = None;
-                }
-                "resume" => {
-                    last_timestamp = Some(timestamp);
+            self.push_system(timestamp, title);
+            return;
+        }
+
+        // rename classes and clean up titles according to the rules in the config
+        let raw_class = class;
+        let class = settings.config.map_class(raw_class);
+        let title = settings.config.map_title(&class, title);
+
+        // a focused locker means nobody is there, the next focus row resumes implicitly
+        if settings.config.is_idle_class(&class) {
+            self.go_idle(timestamp, IdleCause::Focus);
+            return;
+        }
+
+        if settings.is_ignored(&class, &title) {
+            match settings.config.ignore_mode {
+                // pretend the row never happened, the previous window keeps the time
+                IgnoreMode::Drop => {}
+                IgnoreMode::Idle => self.go_idle(timestamp, IdleCause::Focus),
+            }
+            return;
+        }
+
+        self.end_inhibited(timestamp);
+        self.close(timestamp);
+        self.inhibits_idle =
+            settings.config.inhibits_idle(raw_class) || settings.config.inhibits_idle(&class);
+        if let State::Idle {
+            window,
+            cause: IdleCause::Event,
+        } = &mut self.state
+        {
+            self.anomalies.push(Anomaly {
+                timestamp,
+                description: "focus change while idle, without a resume",
+            });
+            if settings.strict {
+                *window = Some((class, title));
+                return;
+            }
+        }
+        self.state = State::Active {
+            since: timestamp,
+            class,
+            title,
+        };
+    }
+
+    fn push_system(&mut self, timestamp: i64, event: &str) {
+        // games and videos don't need input, so an idle row there doesn't mean nobody is watching
+        if event == "idle" && self.inhibits_idle && matches!(self.state, State::Active { .. }) {
+            self.inhibited_since.get_or_insert(timestamp);
+            return;
+        }
+        let inhibited = self.inhibited_since.is_some();
+        self.end_inhibited(timestamp);
+
+        match event {
+            "boot" => {
+                if let State::Active { .. } = self.state {
+                    // no shutdown was logged, there is no way to know when this span ended
+                    self.anomalies.push(Anomaly {
+                        timestamp,
+                        description: "boot without a shutdown, the last span was discarded",
+                    });
                 }
-                "shutdown" | "idle" => {
-                    add_interval_to_map(
-                        last_timestamp,
+                self.state = State::Off;
+            }
+            "shutdown" => {
+                self.close(timestamp);
+                self.state = State::Off;
+            }
+            "idle" => {
+                if let State::Idle {
+                    cause: IdleCause::Event,
+                    ..
+                } = self.state
+                {
+                    self.anomalies.push(Anomaly {
                         timestamp,
-                        last_class.as_ref(),
-                        last_title.as_ref(),
-                        settings,
-                        &mut total,
-                        &mut map,
-                    );
-                    last_timestamp = None;
+                        description: "idle while already idle",
+                    });
                 }
-                _ => {}
+                self.go_idle(timestamp, IdleCause::Event);
             }
-        } else {
-            add_interval_to_map(
-                last_timestamp,
+            "resume" => match std::mem::replace(&mut self.state, State::Off) {
+                State::Idle {
+                    window: Some((class, title)),
+                    ..
+                } => {
+                    self.state = State::Active {
+                        since: timestamp,
+                        class,
+                        title,
+                    };
+                }
+                State::Idle { window: None, .. } => {}
+                // the idle was ignored, so the window never stopped being active
+                state @ State::Active { .. } if inhibited => self.state = state,
+                state => {
+                    self.state = state;
+                    self.anomalies.push(Anomaly {
+                        timestamp,
+                        description: "resume without an idle",
+                    });
+                }
+            },
+            _ => self.anomalies.push(Anomaly {
                 timestamp,
-                last_class.as_ref(),
-                last_title.as_ref(),
-                settings,
-                &mut total,
-                &mut map,
-            );
-
-            last_timestamp = Some(timestamp);
-            last_class = Some(class.clone());
-            last_title = Some(title.clone());
-            if settings.full {
-                last_title = Some(format!("{class}: {title}"));
+                description: "unknown SYSTEM event",
+            }),
+        }
+    }
+
+    /// The finished spans, plus the span that is still open, closed at `now`
+    pub fn parsed(&self, settings: &Settings, now: i64) -> ParsedLog {
+        let mut spans = self.spans.clone();
+        if let State::Active {
+            since,
+            class,
+            title,
+        } = &self.state
+        {
+            if now > *since {
+                spans.push(Span {
+                    start: *since,
+                    end: now,
+                    class: class.clone(),
+                    title: title.clone(),
+                });
+            }
+        }
+
+        if let Some(min_span) = settings.config.min_span {
+            spans = merge_short_spans(spans, min_span as i64);
+        }
+
+        // without idle events a window left focused overnight would get all of that time,
+        // past max_span nobody knows what happened
+        let mut cut = Vec::new();
+        if let Some(max_span) = settings.config.max_span {
+            for span in &mut spans {
+                let duration = (span.end - span.start) as u64;
+                if duration > max_span {
+                    let end = span.start + max_span as i64;
+                    cut.push(Span {
+                        start: end,
+                        end: span.end,
+                        class: span.class.clone(),
+                        title: span.title.clone(),
+                    });
+                    span.end = end;
+                }
+            }
+        }
+
+        let open_inhibited = self.inhibited_since.map_or(0, |since| now - since);
+        ParsedLog {
+            spans,
+            inhibited_idle: self.inhibited_idle + open_inhibited.max(0) as u64,
+            cut,
+            anomalies: self.anomalies.clone(),
+        }
+    }
+
+    fn go_idle(&mut self, timestamp: i64, cause: IdleCause) {
+        self.end_inhibited(timestamp);
+        self.close(timestamp);
+        // the window before a locker or an ignored window lost focus, a resume shouldn't credit it
+        let keep_window = cause == IdleCause::Event;
+        self.state = match std::mem::replace(&mut self.state, State::Off) {
+            State::Active { class, title, .. } => State::Idle {
+                window: Some((class, title)).filter(|_| keep_window),
+                cause,
+            },
+            // an idle row is stronger evidence than a focused locker, keep the strongest cause
+            State::Idle {
+                window,
+                cause: previous,
+            } => State::Idle {
+                window: window.filter(|_| keep_window),
+                cause: if previous == IdleCause::Event {
+                    previous
+                } else {
+                    cause
+                },
+            },
+            State::Off => State::Idle {
+                window: None,
+                cause,
+            },
+        };
+    }
+
+    fn end_inhibited(&mut self, timestamp: i64) {
+        if let Some(since) = self.inhibited_since.take() {
+            self.inhibited_idle += (timestamp - since).max(0) as u64;
+        }
+    }
+
+    // end the current span at `timestamp`, the window stays focused
+    fn close(&mut self, timestamp: i64) {
+        if let State::Active {
+            since,
+            class,
+            title,
+        } = &mut self.state
+        {
+            self.spans.push(Span {
+                start: *since,
+                end: timestamp,
+                class: class.clone(),
+                title: title.clone(),
+            });
+            *since = timestamp;
+        }
+    }
+}
+
+/// Fold spans shorter than `min_span` into the activity around them. A short span extends the
+/// span right before it, or the span after it if nothing came right before, and neighbours with
+/// the same window are joined so alt-tabbing past a window leaves one continuous span.
+fn merge_short_spans(spans: Vec<Span>, min_span: i64) -> Vec<Span> {
+    let mut merged: Vec<Span> = Vec::with_capacity(spans.len());
+    // short spans with nothing before them, waiting for the next span
+    let mut pending: Vec<Span> = Vec::new();
+
+    for mut span in spans {
+        if span.end - span.start < min_span {
+            if pending.is_empty() {
+                if let Some(previous) = merged.last_mut().filter(|p| p.end == span.start) {
+                    previous.end = span.end;
+                    continue;
+                }
+            }
+            if pending.last().is_some_and(|p| p.end != span.start) {
+                merged.append(&mut pending);
+            }
+            pending.push(span);
+            continue;
+        }
+
+        if let Some(first) = pending.first() {
+            if pending.last().unwrap().end == span.start {
+                span.start = first.start;
+                pending.clear();
+            } else {
+                merged.append(&mut pending);
+            }
+        }
+
+        match merged.last_mut() {
+            Some(previous)
+                if previous.end == span.start
+                    && previous.class == span.class
+                    && previous.title == span.title =>
+            {
+                previous.end = span.end;
             }
+            _ => merged.push(span),
         }
     }
 
-    let timestamp = chrono::Utc::now().timestamp_millis() as u64;
-    add_interval_to_map(
-        last_timestamp,
-        timestamp,
-        last_class.as_ref(),
-        last_title.as_ref(),
-        settings,
-        &mut total,
-        &mut map,
-    );
+    merged.append(&mut pending);
+    merged
+}
+
+pub fn parse_log(reader: &mut LogReader, settings: &Settings) -> Result<ParsedLog, Box<dyn Error>> {
+    let mut parser = SpanParser::default();
+
+    let _ = reader.reset();
+    for row in reader {
+        let record = row?;
+        let timestamp: i64 = record[0].parse()?;
+        parser.push(settings, timestamp, &record[1], &record[2]);
+    }
+
+    Ok(parser.parsed(settings, chrono::Utc::now().timestamp_millis()))
+}
+
+pub fn compute_durations(spans: &[Span], settings: &Settings) -> Durations {
+    let mut map: HashMap<String, u64> = HashMap::new();
+    let mut total = 0;
+
+    for span in spans {
+        add_interval_to_map(span, settings, &mut total, &mut map);
+    }
 
     let mut vec: Vec<(String, u64)> = map.into_iter().collect();
-    vec.sort_by(|a, b| b.1.cmp(&a.1));
-    Ok((vec, total))
+    match settings.sort {
+        SortOrder::Duration => vec.sort_by_key(|b| std::cmp::Reverse(b.1)),
+        SortOrder::Name => vec.sort_by_key(|a| a.0.to_lowercase()),
+    }
+    (vec, total)
 }
 
 fn add_interval_to_map(
-    last_timestamp: Option<u64>,
-    end: u64,
-    last_class: Option<&String>,
-    last_title: Option<&String>,
+    span: &Span,
     settings: &Settings,
     total: &mut u64,
     map: &mut HashMap<String, u64>,
 ) {
-    if let (Some(start), Some(class), Some(title)) = (last_timestamp, last_class, last_title) {
-        let duration = (end - start) as u64;
-        *total += duration;
-
-        if settings.full || class == &settings.class_arg {
-            *map.entry(title.clone()).or_default() += duration;
-        } else if settings.class_arg == "" {
-            *map.entry(class.clone()).or_default() += duration;
-        }
+    let duration = (span.end - span.start) as u64;
+    *total += duration;
+
+    if let Some(key) = key(settings, &span.class, &span.title) {
+        *map.entry(key).or_default() += duration;
     }
 }
 
+/// Bucket the activity inside `interval` into `width` sections. With a `label` only the spans of
+/// those keys count, for the timeline of a single row.
 pub fn timeline(
-    reader: &mut LogReader,
+    spans: &[Span],
     width: usize,
+    interval: &Interval,
     settings: &Settings,
-    label: Option<&String>,
-) -> Vec<(String, i64, i64, bool, bool)> {
-    let ms_per_section = (settings.interval.width() / width as u64) as i64;
-    let starting_ms = settings.interval.start.timestamp_millis();
-    let mut sections: Vec<(String, i64, i64, bool, bool)> =
-        vec![(String::from(""), 0, 0, false, false); width];
-
-    let mut last_timestamp: Option<i64> = None;
-    let mut last_class: Option<String> = None;
-    let mut last_title: Option<String> = None;
-
-    let _ = reader.reset();
-    for row in reader {
-        let record = row.unwrap();
-        let timestamp: i64 = record[0].parse().unwrap();
-        let mut class = record[1].to_string();
-        let title = record[2].to_string();
-
-        // filter classes using hashmap to rename them according to config
-        class = match settings.config.class_mappings.get(&class) {
-            Some(filtered_class) => filtered_class.clone(),
-            None => class,
-        };
-
-        if class.contains("steam_app") {
-            class = String::from("steam");
-        }
+    label: Option<&[String]>,
+) -> Vec<Section> {
+    let ms_per_section = ms_per_section(interval, width);
+    let starting_ms = interval.start.timestamp_millis();
+    let mut sections: Vec<Section> = vec![(String::from(""), 0, 0, false, false); width];
 
-        if class == "SYSTEM" {
-            match title.as_str() {
-                "boot" => {
-                    last_timestamp = None;
-                    last_class = None;
-                    last_title = None;
-                }
-                "resume" => {
-                    last_timestamp = Some(timestamp);
-                }
-                "shutdown" | "idle" => {
-                    assign_interval_to_section(
-                        last_timestamp,
-                        timestamp,
-                        last_class.as_ref(),
-                        last_title.as_ref(),
-                        starting_ms,
-                        ms_per_section,
-                        settings,
-                        label,
-                        &mut sections,
-                    );
-                    last_timestamp = None;
-                }
-                _ => {}
-            }
-        } else {
-            assign_interval_to_section(
-                last_timestamp,
-                timestamp,
-                last_class.as_ref(),
-                last_title.as_ref(),
-                starting_ms,
-                ms_per_section,
-                settings,
-                label,
-                &mut sections,
-            );
-            last_timestamp = Some(timestamp);
-            last_class = Some(class.clone());
-            last_title = Some(title);
-        }
+    for span in spans {
+        assign_interval_to_section(
+            span,
+            starting_ms,
+            ms_per_section,
+            settings,
+            label,
+            &mut sections,
+        );
     }
 
-    let timestamp = chrono::Utc::now().timestamp_millis();
-    assign_interval_to_section(
-        last_timestamp,
-        timestamp,
-        last_class.as_ref(),
-        last_title.as_ref(),
-        starting_ms,
-        ms_per_section,
-        settings,
-        label,
-        &mut sections,
-    );
-
     sections
 }
 
 fn assign_interval_to_section(
-    last_timestamp: Option<i64>,
-    timestamp: i64,
-    last_class: Option<&String>,
-    last_title: Option<&String>,
+    span: &Span,
     starting_ms: i64,
     ms_per_section: i64,
     settings: &Settings,
-    label: Option<&String>,
-    sections: &mut Vec<(String, i64, i64, bool, bool)>,
+    label: Option<&[String]>,
+    sections: &mut [Section],
 ) {
-    if let (Some(start), Some(class_name), Some(title)) = (last_timestamp, last_class, last_title) {
-        if settings.multi_timeline && (label.unwrap() == &key(settings, class_name, title))
-            || !settings.multi_timeline
-                && (settings.class_arg == "" || settings.full || &settings.class_arg == class_name)
-        {
-            let edge_detection_padding = (ms_per_section as f64 / 10.0) as i64;
-            let start_index = section_index(starting_ms, ms_per_section, start);
-            let end_index = section_index(starting_ms, ms_per_section, timestamp);
-            for i in start_index..end_index + 1 {
-                let section_start = starting_ms + ms_per_section * i as i64;
-                let section_end = starting_ms + ms_per_section * (i as i64 + 1);
-                let contribution = section_end.min(timestamp) - section_start.max(start);
-                if section_start + edge_detection_padding >= start {
-                    sections[i].3 = true;
-                }
-                if section_end - edge_detection_padding <= timestamp {
-                    sections[i].4 = true;
-                }
-                let key = key(settings, class_name, title);
-                sections[i].2 += contribution;
-                if sections[i].0 == key {
-                    sections[i].1 += contribution;
-                } else {
-                    sections[i].1 -= contribution;
-                    if sections[i].1 < 0 {
-                        sections[i].0 = key.clone();
-                        sections[i].1 *= -1;
-                    }
-                }
+    let Some(key) = key(settings, &span.class, &span.title) else {
+        return;
+    };
+    if label.is_some_and(|keys| !keys.contains(&key)) {
+        return;
+    }
+
+    // clip the span to the timeline, spans can cross day boundaries in stacked mode
+    let timeline_end = starting_ms + ms_per_section * sections.len() as i64;
+    let start = span.start.max(starting_ms);
+    let end = span.end.min(timeline_end);
+    if start >= end {
+        return;
+    }
+
+    let edge_detection_padding = (ms_per_section as f64 / 10.0) as i64;
+    let start_index = section_index(starting_ms, ms_per_section, start);
+    let end_index = section_index(starting_ms, ms_per_section, end - 1);
+    for (i, section) in sections
+        .iter_mut()
+        .enumerate()
+        .take(end_index + 1)
+        .skip(start_index)
+    {
+        let section_start = starting_ms + ms_per_section * i as i64;
+        let section_end = starting_ms + ms_per_section * (i as i64 + 1);
+        let contribution = section_end.min(end) - section_start.max(start);
+        if section_start + edge_detection_padding >= start {
+            section.3 = true;
+        }
+        if section_end - edge_detection_padding <= end {
+            section.4 = true;
+        }
+        section.2 += contribution;
+        if section.0 == key {
+            section.1 += contribution;
+        } else {
+            section.1 -= contribution;
+            if section.1 < 0 {
+                section.0 = key.clone();
+                section.1 *= -1;
             }
         }
     }
 }
 
+pub fn ms_per_section(interval: &Interval, width: usize) -> i64 {
+    // at least a millisecond, section_index divides by it
+    (interval.width() / width as u64).max(1) as i64
+}
+
 fn section_index(starting_ms: i64, ms_per_section: i64, timestamp: i64) -> usize {
     ((timestamp - starting_ms) / ms_per_section) as usize
 }
 
-fn key(settings: &Settings, last_class: &String, last_title: &String) -> String {
+/// The key a span is grouped under, or None if the arguments filter it out
+pub fn key(settings: &Settings, class: &String, title: &String) -> Option<String> {
     if settings.full {
-        format!("{last_class}: {last_title}")
-    } else if settings.class_arg == "" {
-        last_class.clone()
+        Some(format!("{class}: {title}"))
+    } else if !settings.class_arg.is_empty() {
+        (class == &settings.class_arg).then(|| title.clone())
+    } else if !settings.category_arg.is_empty() {
+        (settings.config.category(class, title) == settings.category_arg).then(|| class.clone())
     } else {
-        last_title.clone()
+        match settings.group_by {
+            GroupBy::Class => Some(class.clone()),
+            GroupBy::Category => Some(settings.config.category(class, title).to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    const MIN: i64 = 60_000;
+
+    // (class, start, end)
+    type SpanRows = &'static [(&'static str, i64, i64)];
+
+    // push (minute, class, title) rows and close the log at `end` minutes
+    fn parse(settings: &Settings, rows: &[(i64, &str, &str)], end: i64) -> ParsedLog {
+        let mut parser = SpanParser::default();
+        for (minute, class, title) in rows {
+            parser.push(settings, minute * MIN, class, title);
+        }
+        parser.parsed(settings, end * MIN)
+    }
+
+    fn spans(log: &ParsedLog) -> Vec<(&str, i64, i64)> {
+        log.spans
+            .iter()
+            .map(|span| (span.class.as_str(), span.start / MIN, span.end / MIN))
+            .collect()
+    }
+
+    #[test]
+    fn resume_after_a_locker_waits_for_the_next_focus() {
+        let settings = Settings::new(Config::without_file());
+        let log = parse(
+            &settings,
+            &[
+                (0, "firefox", ""),
+                (10, "SYSTEM", "idle"),
+                (11, "hyprlock", ""),
+                (21, "SYSTEM", "resume"),
+                (22, "kitty", ""),
+            ],
+            30,
+        );
+        assert_eq!(spans(&log), [("firefox", 0, 10), ("kitty", 22, 30)]);
+        assert!(log.anomalies.is_empty());
+    }
+
+    #[test]
+    fn focus_after_idle_resumes_implicitly() {
+        let settings = Settings::new(Config::without_file());
+        let log = parse(
+            &settings,
+            &[
+                (0, "firefox", ""),
+                (10, "SYSTEM", "idle"),
+                (20, "kitty", ""),
+            ],
+            30,
+        );
+        assert_eq!(spans(&log), [("firefox", 0, 10), ("kitty", 20, 30)]);
+        assert_eq!(log.anomalies[0].timestamp, 20 * MIN);
+    }
+
+    #[test]
+    fn strict_mode_waits_for_the_resume() {
+        let mut settings = Settings::new(Config::without_file());
+        settings.strict = true;
+        let log = parse(
+            &settings,
+            &[
+                (0, "firefox", ""),
+                (10, "SYSTEM", "idle"),
+                (20, "kitty", ""),
+                (25, "SYSTEM", "resume"),
+            ],
+            30,
+        );
+        assert_eq!(spans(&log), [("firefox", 0, 10), ("kitty", 25, 30)]);
+        assert_eq!(log.anomalies.len(), 1);
+    }
+
+    #[test]
+    fn duplicate_idle_is_an_anomaly() {
+        let settings = Settings::new(Config::without_file());
+        let log = parse(
+            &settings,
+            &[
+                (0, "firefox", ""),
+                (10, "SYSTEM", "idle"),
+                (15, "SYSTEM", "idle"),
+                (20, "SYSTEM", "resume"),
+            ],
+            30,
+        );
+        assert_eq!(spans(&log), [("firefox", 0, 10), ("firefox", 20, 30)]);
+        assert_eq!(log.anomalies[0].description, "idle while already idle");
+    }
+
+    #[test]
+    fn resume_without_idle_keeps_the_span_open() {
+        let settings = Settings::new(Config::without_file());
+        let log = parse(
+            &settings,
+            &[(0, "firefox", ""), (10, "SYSTEM", "resume")],
+            30,
+        );
+        assert_eq!(spans(&log), [("firefox", 0, 30)]);
+        assert_eq!(log.anomalies[0].description, "resume without an idle");
+    }
+
+    #[test]
+    fn boot_without_shutdown_discards_the_open_span() {
+        let settings = Settings::new(Config::without_file());
+        let log = parse(
+            &settings,
+            &[
+                (0, "firefox", ""),
+                (10, "kitty", ""),
+                (20, "SYSTEM", "boot"),
+                (25, "kitty", ""),
+            ],
+            30,
+        );
+        assert_eq!(spans(&log), [("firefox", 0, 10), ("kitty", 25, 30)]);
+        assert_eq!(log.anomalies[0].timestamp, 20 * MIN);
+    }
+
+    #[test]
+    fn idle_is_ignored_while_an_inhibiting_window_is_focused() {
+        let settings = Settings::new(Config::without_file());
+        let log = parse(
+            &settings,
+            &[
+                (0, "mpv", ""),
+                (10, "SYSTEM", "idle"),
+                (20, "SYSTEM", "resume"),
+            ],
+            30,
+        );
+        assert_eq!(spans(&log), [("mpv", 0, 30)]);
+        assert_eq!(log.inhibited_idle, 10 * MIN as u64);
+        assert!(log.anomalies.is_empty());
+    }
+
+    #[test]
+    fn merge_short_spans_cases() {
+        let span = |(class, start, end): (&str, i64, i64)| Span {
+            start,
+            end,
+            class: class.to_string(),
+            title: String::new(),
+        };
+        #[rustfmt::skip]
+        let cases: &[(&str, SpanRows, SpanRows)] = &[
+            ("short span at the start",
+             &[("a", 0, 1), ("b", 1, 10)],
+             &[("b", 0, 10)]),
+            ("several short spans at the start",
+             &[("a", 0, 1), ("b", 1, 2), ("c", 2, 10)],
+             &[("c", 0, 10)]),
+            ("short span at the end",
+             &[("a", 0, 10), ("b", 10, 11)],
+             &[("a", 0, 11)]),
+            ("A-B-A flicker",
+             &[("a", 0, 10), ("b", 10, 11), ("a", 11, 20)],
+             &[("a", 0, 20)]),
+            ("short span alone between idle gaps",
+             &[("a", 0, 10), ("b", 12, 13), ("c", 20, 30)],
+             &[("a", 0, 10), ("b", 12, 13), ("c", 20, 30)]),
+            ("short span after an idle gap",
+             &[("a", 0, 10), ("b", 12, 13), ("c", 13, 20)],
+             &[("a", 0, 10), ("c", 12, 20)]),
+            ("short spans split by an idle gap",
+             &[("a", 0, 1), ("b", 5, 6), ("c", 6, 20)],
+             &[("a", 0, 1), ("c", 5, 20)]),
+            ("long spans are left alone",
+             &[("a", 0, 10), ("b", 10, 20), ("a", 20, 30)],
+             &[("a", 0, 10), ("b", 10, 20), ("a", 20, 30)]),
+        ];
+
+        for (name, spans, expected) in cases {
+            let spans = spans.iter().copied().map(span).collect();
+            let merged: Vec<_> = merge_short_spans(spans, 5)
+                .into_iter()
+                .map(|s| (s.class, s.start, s.end))
+                .collect();
+            let expected: Vec<_> = expected
+                .iter()
+                .map(|(class, start, end)| (class.to_string(), *start, *end))
+                .collect();
+            assert_eq!(merged, expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn unaccounted_time_only_counts_the_reported_keys() {
+        let mut settings = Settings::new(Config::without_file());
+        settings.config.max_span = Some(30 * MIN as u64);
+        let log = parse(
+            &settings,
+            &[(0, "firefox", ""), (60, "kitty", ""), (100, "kitty", "")],
+            150,
+        );
+        assert_eq!(
+            spans(&log),
+            [("firefox", 0, 30), ("kitty", 60, 90), ("kitty", 100, 130)]
+        );
+        assert_eq!(log.unaccounted(&settings), 60 * MIN as u64);
+
+        settings.class_arg = String::from("kitty");
+        assert_eq!(log.unaccounted(&settings), 30 * MIN as u64);
+        settings.class_arg = String::from("mpv");
+        assert_eq!(log.unaccounted(&settings), 0);
+    }
+
+    #[test]
+    fn sections_are_never_empty() {
+        let interval = Interval {
+            start: chrono::DateTime::from_timestamp_millis(0).unwrap(),
+            end: chrono::DateTime::from_timestamp_millis(10).unwrap(),
+        };
+        assert_eq!(ms_per_section(&interval, 5), 2);
+        assert_eq!(ms_per_section(&interval, 100), 1);
     }
 }
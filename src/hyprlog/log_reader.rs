@@ -1,10 +1,12 @@
-use anyhow::{Context, Result};
-use chrono::TimeDelta;
+use anyhow::{Context, Result, anyhow};
+use chrono::{TimeDelta, Utc};
 use csv::{Reader, StringRecord};
 use directories::BaseDirs;
 use std::{
+    collections::HashMap,
     fs::{File, create_dir_all},
-    path::PathBuf,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
 };
 
 use crate::{Interval, Settings};
@@ -17,28 +19,44 @@ pub struct LogReader {
     interval: Interval,
 }
 
+/// Where hyprlogd writes the day files
+fn data_dir() -> PathBuf {
+    BaseDirs::new()
+        .map(|b| b.data_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("hyprlog")
+}
+
+/// The file hyprlogd is writing to right now, the daemon names them by the UTC date
+pub fn today_file() -> PathBuf {
+    data_dir().join(format!("{}.csv", Utc::now().format("%Y-%m-%d")))
+}
+
+// the day files that can have rows in the interval, whether they exist or not
+fn day_files(interval: &Interval) -> Vec<PathBuf> {
+    let base_dir = data_dir();
+
+    create_dir_all(&base_dir).expect("failed to create data directory");
+    // get all files in the interval
+    let start = interval.start.date_naive();
+    let end = interval.end.date_naive();
+
+    let mut files = Vec::new();
+    let mut current = start;
+
+    while current <= end {
+        files.push(base_dir.join(format!("{}.csv", current.format("%Y-%m-%d"))));
+        current += TimeDelta::days(1);
+    }
+    files
+}
+
 impl LogReader {
     pub fn new(settings: &Settings) -> Self {
-        let base_dir = BaseDirs::new()
-            .map(|b| b.data_dir().to_path_buf())
-            .unwrap_or_else(|| PathBuf::from("/tmp"))
-            .join("hyprlog");
-
-        create_dir_all(&base_dir).expect("failed to create data directory");
-        // get all files in the interval
-        let start = settings.interval.start.date_naive();
-        let end = settings.interval.end.date_naive();
-
-        let mut files = Vec::new();
-        let mut current = start;
-
-        while current <= end {
-            files.push(base_dir.join(format!("{}.csv", current.format("%Y-%m-%d"))));
-            current += TimeDelta::days(1);
-        }
+        let mut files = day_files(&settings.interval);
 
         // skip non-existent files
-        files = files.into_iter().filter(|p| p.exists()).collect();
+        files.retain(|p| p.exists());
 
         Self {
             files,
@@ -105,7 +123,7 @@ impl LogReader {
                 }
                 if let Err(e) = self.open_current() {
                     // Skip unreadable file and try next
-                    if self.advance_file().ok()? == false {
+                    if !self.advance_file().ok()? {
                         return Some(Err(e));
                     }
                     continue;
@@ -130,9 +148,7 @@ impl LogReader {
                     if let Err(e) = self.advance_file() {
                         return Some(Err(e));
                     }
-                    if self.rdr.is_none() {
-                        return None; // no more files
-                    }
+                    self.rdr.as_ref()?; // no more files
                     continue;
                 }
                 Err(err) => return Some(Err(err.into())),
@@ -152,3 +168,175 @@ impl Iterator for LogReader {
         self.next_record()
     }
 }
+
+/// Follows the day files of an interval, every call to `read_new` only reads the rows that were
+/// added since the last one, so a long interval isn't read again whenever today's file grows.
+pub struct LogTail {
+    interval: Interval,
+    // how far each file has been read
+    offsets: HashMap<PathBuf, u64>,
+}
+
+impl LogTail {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            interval: settings.interval.clone(),
+            offsets: HashMap::new(),
+        }
+    }
+
+    /// The rows inside the interval that were added since the last call, oldest first. Rows that
+    /// can't be read are skipped and returned as errors, they don't hold up the rows after them.
+    pub fn read_new(&mut self) -> (Vec<StringRecord>, Vec<anyhow::Error>) {
+        let mut records = Vec::new();
+        let mut errors = Vec::new();
+        for path in day_files(&self.interval) {
+            if let Err(e) = self.read_file(&path, &mut records, &mut errors) {
+                errors.push(e.context(format!(
+                    "failed to read log file {}",
+                    path.to_string_lossy()
+                )));
+            }
+        }
+        (records, errors)
+    }
+
+    // the offset only moves on once every new row of the file is in `records` or `errors`, when
+    // the file can't be read at all it is tried again next time
+    fn read_file(
+        &mut self,
+        path: &Path,
+        records: &mut Vec<StringRecord>,
+        errors: &mut Vec<anyhow::Error>,
+    ) -> Result<()> {
+        let Ok(mut file) = File::open(path) else {
+            return Ok(());
+        };
+        let offset = self.offsets.get(path).copied().unwrap_or_default();
+        if file.metadata()?.len() <= offset {
+            return Ok(());
+        }
+        file.seek(SeekFrom::Start(offset))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        // a row that's still being written is read next time
+        let Some(end) = bytes.iter().rposition(|b| *b == b'\n') else {
+            return Ok(());
+        };
+        bytes.truncate(end + 1);
+
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(offset == 0)
+            .flexible(true)
+            .from_reader(bytes.as_slice());
+        for record in rdr.records() {
+            let record = match record {
+                Ok(record) if record.len() == 3 => record,
+                Ok(record) => {
+                    errors.push(anyhow!(
+                        "skipped a row of {} with {} fields instead of 3",
+                        path.to_string_lossy(),
+                        record.len()
+                    ));
+                    continue;
+                }
+                Err(e) => {
+                    errors.push(
+                        anyhow::Error::new(e)
+                            .context(format!("skipped a row of {}", path.to_string_lossy())),
+                    );
+                    continue;
+                }
+            };
+            if record[0]
+                .parse::<u64>()
+                .is_ok_and(|timestamp| self.interval.contains_utc_timestamp_millis(timestamp))
+            {
+                records.push(record);
+            }
+        }
+        self.offsets
+            .insert(path.to_path_buf(), offset + bytes.len() as u64);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+
+    const HEADER: &str = "timestamp,class,title\n";
+
+    // an interval around the timestamps used below
+    fn tail() -> LogTail {
+        LogTail {
+            interval: Interval {
+                start: chrono::DateTime::from_timestamp_millis(0).unwrap(),
+                end: chrono::DateTime::from_timestamp_millis(10_000).unwrap(),
+            },
+            offsets: HashMap::new(),
+        }
+    }
+
+    // a day file in a directory of its own, removed with `remove`
+    fn log_file(name: &str, contents: &str) -> PathBuf {
+        crate::test_files::temp_dir(name, &[(name, contents)]).join(name)
+    }
+
+    fn remove(path: PathBuf) {
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    fn append(path: &Path, contents: &str) {
+        let mut file = OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+    }
+
+    fn read(tail: &mut LogTail, path: &Path) -> (Vec<String>, usize) {
+        let mut records = Vec::new();
+        let mut errors = Vec::new();
+        tail.read_file(path, &mut records, &mut errors).unwrap();
+        let classes = records.iter().map(|record| record[1].to_string()).collect();
+        (classes, errors.len())
+    }
+
+    #[test]
+    fn partial_last_line_is_read_once_it_is_complete() {
+        let path = log_file("partial.csv", &format!("{HEADER}1000,kitty,a\n2000,fire"));
+        let mut tail = tail();
+        assert_eq!(read(&mut tail, &path), (vec![String::from("kitty")], 0));
+
+        append(&path, "fox,b\n");
+        assert_eq!(read(&mut tail, &path), (vec![String::from("firefox")], 0));
+        assert_eq!(read(&mut tail, &path), (vec![], 0));
+        remove(path);
+    }
+
+    #[test]
+    fn header_only_file_is_not_read_again_as_a_row() {
+        let path = log_file("header.csv", HEADER);
+        let mut tail = tail();
+        assert_eq!(read(&mut tail, &path), (vec![], 0));
+
+        append(&path, "1000,kitty,a\n");
+        assert_eq!(read(&mut tail, &path), (vec![String::from("kitty")], 0));
+        remove(path);
+    }
+
+    #[test]
+    fn bad_row_is_reported_once_and_skipped() {
+        let path = log_file(
+            "bad.csv",
+            &format!("{HEADER}1000,kitty,a\nbroken\n2000,firefox,b\n"),
+        );
+        let mut tail = tail();
+        assert_eq!(
+            read(&mut tail, &path),
+            (vec![String::from("kitty"), String::from("firefox")], 1)
+        );
+        assert_eq!(read(&mut tail, &path), (vec![], 0));
+        remove(path);
+    }
+}
@@ -1,86 +1,369 @@
+mod bar;
 mod config;
+mod config_file;
 mod daemon_commands;
+mod export;
+mod glyphs;
+mod html;
 mod interval;
 mod log_parsing;
 mod log_reader;
+#[path = "../sockets.rs"]
+mod sockets;
+mod svg;
+#[cfg(test)]
+mod test_files;
 mod view;
+mod watch;
 
 use daemon_commands::send_command;
 use std::env;
+use std::path::PathBuf;
 use view::render_log;
 
-use crate::{config::Config, interval::Interval};
+use crate::{
+    config::{Config, Pattern, ReportOptions},
+    glyphs::{GLYPH_SETS, Glyphs},
+    interval::Interval,
+    svg::SvgOptions,
+};
 
 fn main() {
     // use chrono::Utc;
     // let start = Utc::now().timestamp_millis();
+    // exit quietly instead of panicking when the reading end of a pipe closes, like `hyprlog | head`
+    // SAFETY: nothing else runs yet, and SIG_DFL is a valid disposition for SIGPIPE
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
     let args: Vec<String> = env::args().collect();
     match args.get(1).map(String::as_str) {
         Some("--idle") => send_command("idle"),
         Some("--resume") => send_command("resume"),
         Some("--help") | Some("-h") => {
-            print_usage();
+            println!("{}", usage(&Config::new()));
+        }
+        Some("config") => match args.get(2).map(String::as_str) {
+            Some("check") => check_config(),
+            _ => {
+                eprintln!("Usage: hyprlog config check");
+                std::process::exit(1);
+            }
+        },
+        Some("--list-presets") => {
+            for (name, _) in &Config::new().presets {
+                println!("{name}");
+            }
         }
         None => {
-            view::render_log(&Settings::new());
+            view::render_log(&Settings::new(Config::new()));
         }
         _ => {
-            let mut settings = Settings::new();
-            // Are we waiting on values for these args?
-            let mut class = false;
-            let mut days = false;
-            for arg in args.iter().skip(1) {
-                if class {
-                    settings.class_arg = match settings.config.class_mappings.get(arg) {
-                        Some(filtered_class) => filtered_class.clone(),
-                        None => arg.clone(),
-                    };
-                    class = false;
-                } else if days {
-                    match arg.clone().parse::<u64>() {
-                        Ok(day_count) => {
-                            settings.interval.set_days(day_count);
-                            days = false;
+            let config = if args.iter().any(|arg| arg == "--no-config") {
+                Config::without_file()
+            } else {
+                Config::new()
+            };
+            let mut settings = Settings::new(config);
+            // the bar's default, unless the config has its own; presets and flags come after
+            if args.get(1).is_some_and(|arg| arg == "bar") && settings.config.defaults.top.is_none()
+            {
+                settings.top = Some(bar::BAR_TOP);
+            }
+            // presets are applied before any flag, so flags override them wherever they are
+            let Some(presets) = preset_names(&args[1..]) else {
+                usage_error("Please provide a preset name for the preset argument.")
+            };
+            for name in presets {
+                let Some(preset) = settings.config.preset(name).cloned() else {
+                    usage_error(format!(
+                        "Unknown preset: {name}{}",
+                        preset_list(&settings.config)
+                    ));
+                };
+                settings.apply(&preset);
+            }
+
+            // `report --html FILE`, `timeline --svg FILE`, `bar` and `watch` take the same
+            // arguments as the terminal report
+            let command = args
+                .get(1)
+                .map(String::as_str)
+                .filter(|command| ["report", "timeline", "bar", "watch"].contains(command));
+            let mut html = None;
+            let mut svg = None;
+            let mut svg_options = SvgOptions::default();
+            let mut follow = false;
+            let mut args = args.iter().skip(if command.is_some() { 2 } else { 1 });
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--html" if command == Some("report") => {
+                        let Some(path) = args.next() else {
+                            usage_error("Please provide a file for the html argument.")
+                        };
+                        html = Some(PathBuf::from(path));
+                    }
+                    "--svg" if command == Some("timeline") => {
+                        let Some(path) = args.next() else {
+                            usage_error("Please provide a file for the svg argument.")
+                        };
+                        svg = Some(PathBuf::from(path));
+                    }
+                    "--svg-width" if command == Some("timeline") => {
+                        let Some(width) = args.next() else {
+                            usage_error("Please provide a pixel count for the svg-width argument.")
+                        };
+                        match width.parse::<u32>() {
+                            Ok(width) if width > 0 => svg_options.width = width,
+                            _ => usage_error("Invalid value for the svg-width argument."),
+                        }
+                    }
+                    "--svg-height" if command == Some("timeline") => {
+                        let Some(height) = args.next() else {
+                            usage_error("Please provide a pixel count for the svg-height argument.")
+                        };
+                        match height.parse::<u32>() {
+                            Ok(height) if height > 0 => svg_options.height = height,
+                            _ => usage_error("Invalid value for the svg-height argument."),
                         }
-                        Err(_) => {
-                            println!("Invalid value for the days argument.");
-                            return;
+                    }
+                    "--no-labels" if command == Some("timeline") => {
+                        svg_options.labels = false;
+                    }
+                    "--follow" if command == Some("bar") => {
+                        follow = true;
+                    }
+                    "--format" if command == Some("watch") => {
+                        usage_error("The format argument can't be used with watch.")
+                    }
+                    "--class" | "-c" => {
+                        let Some(class) = args.next() else {
+                            usage_error("Please provide a class name for the class argument.")
+                        };
+                        settings.class_arg = settings.config.map_class(class);
+                        settings.category_arg.clear();
+                    }
+                    "--category" => {
+                        let Some(category) = args.next() else {
+                            usage_error("Please provide a category name for the category argument.")
+                        };
+                        settings.category_arg = category.clone();
+                        settings.class_arg.clear();
+                    }
+                    "--by" | "-b" => {
+                        match args.next().map(String::as_str) {
+                            Some("class") => settings.group_by = GroupBy::Class,
+                            Some("category") => settings.group_by = GroupBy::Category,
+                            Some(_) => usage_error(
+                                "Invalid value for the by argument, expected class or category.",
+                            ),
+                            None => {
+                                usage_error("Please provide class or category for the by argument.")
+                            }
                         }
+                        // grouping only applies without a class or category, so drop a default one
+                        settings.class_arg.clear();
+                        settings.category_arg.clear();
                     }
-                } else {
-                    match arg.as_str() {
-                        "--class" | "-c" => {
-                            class = true;
+                    "--sort" => match args.next().map(String::as_str) {
+                        Some("duration") => settings.sort = SortOrder::Duration,
+                        Some("name") => settings.sort = SortOrder::Name,
+                        Some(_) => usage_error(
+                            "Invalid value for the sort argument, expected duration or name.",
+                        ),
+                        None => {
+                            usage_error("Please provide duration or name for the sort argument.")
+                        }
+                    },
+                    "--glyphs" => {
+                        let Some(name) = args.next() else {
+                            usage_error("Please provide a glyph set for the glyphs argument.")
+                        };
+                        match Glyphs::named(name) {
+                            Some(glyphs) => settings.glyphs = glyphs,
+                            None => usage_error(format!(
+                                "Invalid value for the glyphs argument, expected one of {}.",
+                                GLYPH_SETS.join(", ")
+                            )),
                         }
-                        "--days" | "-d" => {
-                            days = true;
+                    }
+                    "--color" => {
+                        let Some(choice) = args.next() else {
+                            usage_error(
+                                "Please provide auto, always or never for the color argument.",
+                            )
+                        };
+                        let Some(choice) = ColorChoice::parse(choice) else {
+                            usage_error(
+                                "Invalid value for the color argument, expected auto, always or never.",
+                            )
+                        };
+                        settings.color = choice;
+                    }
+                    arg if arg.starts_with("--color=") => {
+                        let Some(choice) = ColorChoice::parse(&arg["--color=".len()..]) else {
+                            usage_error(
+                                "Invalid value for the color argument, expected auto, always or never.",
+                            )
+                        };
+                        settings.color = choice;
+                    }
+                    "--width" | "-w" => {
+                        let Some(width) = args.next() else {
+                            usage_error("Please provide a column count for the width argument.")
+                        };
+                        match width.parse::<usize>() {
+                            Ok(width) if width > 0 => settings.width = Some(width),
+                            _ => usage_error("Invalid value for the width argument."),
                         }
-                        "--full" | "-f" => {
-                            settings.full = true;
+                    }
+                    "--format" => {
+                        let Some(format) = args.next() else {
+                            usage_error("Please provide a format for the format argument.")
+                        };
+                        match OutputFormat::parse(format) {
+                            Some(format) => settings.format = format,
+                            None => usage_error(
+                                "Invalid value for the format argument, expected terminal, json, csv, tsv or markdown.",
+                            ),
                         }
-                        "--multi" | "-m" => {
-                            settings.multi_timeline = true;
+                    }
+                    "--buckets" => {
+                        let Some(buckets) = args.next() else {
+                            usage_error("Please provide a bucket count for the buckets argument.")
+                        };
+                        match buckets.parse::<usize>() {
+                            Ok(buckets) if buckets > 0 => settings.buckets = Some(buckets),
+                            _ => usage_error("Invalid value for the buckets argument."),
                         }
-
-                        arg => {
-                            eprintln!("Unknown argument: {arg}");
-                            print_usage();
-                            std::process::exit(1);
+                    }
+                    "--no-config" => {}
+                    "--preset" => {
+                        args.next();
+                    }
+                    arg if arg.starts_with('@') => {}
+                    "--days" | "-d" => {
+                        let Some(days) = args.next() else {
+                            usage_error("Please provide a day count for the days argument.")
+                        };
+                        match days.parse::<u64>() {
+                            Ok(day_count) => settings.interval.set_days(day_count),
+                            Err(_) => usage_error("Invalid value for the days argument."),
                         }
                     }
+                    "--exclude" | "-e" => {
+                        let Some(pattern) = args.next() else {
+                            usage_error("Please provide a class pattern for the exclude argument.")
+                        };
+                        settings.excludes.push(Pattern::new(pattern));
+                    }
+                    "--top" | "-t" => {
+                        let Some(top) = args.next() else {
+                            usage_error("Please provide a row count for the top argument.")
+                        };
+                        match top.parse::<usize>() {
+                            Ok(top) => settings.top = Some(top),
+                            Err(_) => usage_error("Invalid value for the top argument."),
+                        }
+                    }
+                    "--min-percent" => {
+                        let Some(percent) = args.next() else {
+                            usage_error("Please provide a percentage for the min-percent argument.")
+                        };
+                        match config::parse_percent(percent) {
+                            Some(percent) => settings.min_percent = Some(percent),
+                            None => usage_error(
+                                "Invalid value for the min-percent argument, expected a percentage from 0 to 100.",
+                            ),
+                        }
+                    }
+                    "--all" | "-a" => {
+                        settings.top = None;
+                        settings.min_percent = None;
+                    }
+                    "--full" | "-f" => {
+                        settings.full = true;
+                    }
+                    "--multi" | "-m" => {
+                        settings.multi_timeline = true;
+                    }
+                    "--stacked" | "-s" => {
+                        settings.stacked = true;
+                    }
+                    "--patterns" | "-p" => {
+                        settings.patterns = true;
+                    }
+                    // turn off what the config or a preset turned on
+                    "--no-full" => {
+                        settings.full = false;
+                    }
+                    "--no-multi" => {
+                        settings.multi_timeline = false;
+                    }
+                    "--no-stacked" => {
+                        settings.stacked = false;
+                    }
+                    "--no-patterns" => {
+                        settings.patterns = false;
+                    }
+                    "--inhibited" => {
+                        settings.show_inhibited = true;
+                    }
+                    "--strict" => {
+                        settings.strict = true;
+                    }
+
+                    arg => {
+                        usage_error(format!(
+                            "Unknown argument: {arg}\n{}",
+                            usage(&settings.config)
+                        ));
+                    }
                 }
             }
-
-            if class {
-                println!("Please provied a class name for the class argument.");
-                return;
+            // --format can come from the config, so this is only known after all the arguments.
+            // A file has one header, so the sections can't follow the rows.
+            if settings.buckets.is_some()
+                && matches!(settings.format, OutputFormat::Csv | OutputFormat::Tsv)
+            {
+                usage_error(
+                    "The buckets argument doesn't work with csv or tsv, use --format json or markdown.",
+                );
             }
-            if days {
-                println!("Please provied a day count for the days argument.");
-                return;
+
+            // --days can come after --buckets, so the interval is only known here
+            let max_buckets = (settings.interval.width() / 60_000) as usize;
+            if settings
+                .buckets
+                .is_some_and(|buckets| buckets > max_buckets)
+            {
+                usage_error(format!(
+                    "There can be at most {max_buckets} buckets, one per minute of the interval."
+                ));
             }
 
-            render_log(&settings);
+            match command {
+                Some("report") => match html {
+                    Some(path) => html::write_report(&settings, &path),
+                    None => {
+                        eprintln!("Usage: hyprlog report --html FILE [ARGUMENTS]");
+                        std::process::exit(1);
+                    }
+                },
+                Some("timeline") => match svg {
+                    Some(path) => svg::write_timeline(&settings, &path, &svg_options),
+                    None => {
+                        eprintln!(
+                            "Usage: hyprlog timeline --svg FILE [ --svg-width PIXELS ] [ --svg-height PIXELS ] [ --no-labels ] [ARGUMENTS]"
+                        );
+                        std::process::exit(1);
+                    }
+                },
+                Some("bar") if follow => bar::follow(&mut settings),
+                Some("bar") => bar::print_status(&mut settings),
+                Some("watch") => watch::watch(&mut settings),
+                _ => render_log(&settings),
+            }
         }
     }
 
@@ -88,35 +371,340 @@ fn main() {
     // println!("Runtime: {}ms", end - start)
 }
 
-fn print_usage() {
-    println!(
+fn usage(config: &Config) -> String {
+    format!(
         "Usage: hyprlog\n
         [ --help | -h ]\n
-        [ --full | -f ]\n
-        [ --multi | -m ]\n
+        [ @PRESET | --preset PRESET ]\n
+        [ --full | -f | --no-full ]\n
+        [ --multi | -m | --no-multi ]\n
+        [ --stacked | -s | --no-stacked ]\n
+        [ --patterns | -p | --no-patterns ]\n
+        [ --inhibited ]\n
+        [ --strict ]\n
         [ --days DAY_COUNT | -d DAY_COUNT ]\n
         [ --class CLASS_NAME | -c CLASS_NAME ]\n
-        [ --idle | --resume]"
-    );
+        [ --by class|category | -b class|category ]\n
+        [ --category CATEGORY_NAME ]\n
+        [ --exclude CLASS_PATTERN | -e CLASS_PATTERN ]\n
+        [ --top ROW_COUNT | -t ROW_COUNT ]\n
+        [ --min-percent PERCENT ]\n
+        [ --all | -a ]\n
+        [ --sort duration|name ]\n
+        [ --glyphs fancy|blocks|ascii ]\n
+        [ --color=auto|always|never ]\n
+        [ --width COLUMNS | -w COLUMNS ]\n
+        [ --format terminal|json|csv|tsv|markdown ]\n
+        [ --buckets BUCKET_COUNT ]\n
+        [ --no-config ]\n
+        [ --idle | --resume]\n
+        [ report --html FILE ]\n
+        [ timeline --svg FILE [ --svg-width PIXELS ] [ --svg-height PIXELS ] [ --no-labels ] ]\n
+        [ bar [ --follow ] ]\n
+        [ watch ]\n
+        [ config check ]{}",
+        preset_list(config)
+    )
+}
+
+/// Prints an argument error to stderr and exits, scripts check the exit code
+fn usage_error(message: impl std::fmt::Display) -> ! {
+    eprintln!("{message}");
+    std::process::exit(1);
+}
+
+/// Prints the problems in hyprlog.conf to stderr and the effective config to stdout
+fn check_config() {
+    let path = config::config_path();
+    let (config, diagnostics) = Config::load();
+    if !path.exists() {
+        eprintln!(
+            "No config found at {}, using the built in defaults.\n",
+            path.display()
+        );
+    }
+    print!("{config}");
+    if !diagnostics.is_empty() {
+        eprintln!();
+        for diagnostic in &diagnostics {
+            eprintln!("{diagnostic}");
+        }
+        eprintln!(
+            "\nFound {} problem(s) in {}.",
+            diagnostics.len(),
+            path.display()
+        );
+        std::process::exit(1);
+    }
+}
+
+// flags that take the next argument as their value
+const VALUE_FLAGS: &[&str] = &[
+    "--preset",
+    "--class",
+    "-c",
+    "--category",
+    "--by",
+    "-b",
+    "--sort",
+    "--glyphs",
+    "--color",
+    "--width",
+    "-w",
+    "--format",
+    "--buckets",
+    "--days",
+    "-d",
+    "--exclude",
+    "-e",
+    "--top",
+    "-t",
+    "--min-percent",
+    "--html",
+    "--svg",
+    "--svg-width",
+    "--svg-height",
+];
+
+/// The presets given as `@NAME` or `--preset NAME`, in order. The values of other flags are
+/// skipped, `--class @home` is a class. None when `--preset` is missing its name.
+fn preset_names(args: &[String]) -> Option<Vec<&str>> {
+    let mut names = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(name) = arg.strip_prefix('@') {
+            names.push(name);
+        } else if VALUE_FLAGS.contains(&arg.as_str()) {
+            let value = args.next();
+            if arg == "--preset" {
+                names.push(value?.as_str());
+            }
+        }
+    }
+    Some(names)
+}
+
+// the list of presets that ends the usage, empty without any
+fn preset_list(config: &Config) -> String {
+    if config.presets.is_empty() {
+        return String::new();
+    }
+    let names: Vec<String> = config
+        .presets
+        .iter()
+        .map(|(name, _)| format!("@{name}"))
+        .collect();
+    format!("\n\nPresets: {}", names.join(", "))
 }
 
 pub struct Settings {
     //<Tz> {
     pub full: bool,
     pub multi_timeline: bool,
+    pub stacked: bool,
+    pub patterns: bool,
+    pub show_inhibited: bool,
+    pub strict: bool,
     pub class_arg: String,
+    pub category_arg: String,
+    pub group_by: GroupBy,
+    pub sort: SortOrder,
+    pub glyphs: Glyphs,
+    pub color: ColorChoice,
+    /// Columns to draw in, the terminal width when not set
+    pub width: Option<usize>,
+    pub format: OutputFormat,
+    /// Timeline sections to include in --format output
+    pub buckets: Option<usize>,
+    pub excludes: Vec<Pattern>,
+    pub top: Option<usize>,
+    pub min_percent: Option<f64>,
     pub interval: Interval, //<Tz>,
     pub config: Config,
 }
 
 impl Settings {
-    fn new() -> Self {
-        Self {
+    /// Settings with the report defaults from the config applied
+    fn new(config: Config) -> Self {
+        let defaults = config.defaults.clone();
+        let mut settings = Self {
             full: false,
             multi_timeline: false,
+            stacked: false,
+            patterns: false,
+            show_inhibited: false,
+            strict: false,
             class_arg: String::from(""),
+            category_arg: String::from(""),
+            group_by: GroupBy::Class,
+            sort: SortOrder::Duration,
+            glyphs: config.glyphs.clone().unwrap_or_else(Glyphs::detect),
+            color: ColorChoice::Auto,
+            width: None,
+            format: OutputFormat::Terminal,
+            buckets: None,
+            excludes: Vec::new(),
+            top: None,
+            min_percent: None,
             interval: Interval::default(),
-            config: Config::new(),
+            config,
+        };
+        settings.apply(&defaults);
+        settings
+    }
+
+    fn apply(&mut self, options: &ReportOptions) {
+        if let Some(full) = options.full {
+            self.full = full;
+        }
+        if let Some(multi) = options.multi {
+            self.multi_timeline = multi;
+        }
+        if let Some(stacked) = options.stacked {
+            self.stacked = stacked;
+        }
+        if let Some(patterns) = options.patterns {
+            self.patterns = patterns;
+        }
+        if let Some(days) = options.days {
+            self.interval.set_days(days);
+        }
+        // a preset's `by` replaces a default class, but a class next to it still wins
+        if let Some(by) = options.by {
+            self.group_by = by;
+            self.class_arg.clear();
+            self.category_arg.clear();
+        }
+        if let Some(class) = &options.class {
+            self.class_arg = self.config.map_class(class);
+        }
+        if let Some(sort) = options.sort {
+            self.sort = sort;
+        }
+        if let Some(format) = options.format {
+            self.format = format;
+        }
+        if options.top.is_some() {
+            self.top = options.top;
+        }
+        if options.min_percent.is_some() {
+            self.min_percent = options.min_percent;
+        }
+    }
+
+    /// Ignored by the config or excluded on the command line
+    pub fn is_ignored(&self, class: &str, title: &str) -> bool {
+        self.config.is_ignored(class, title) || self.excludes.iter().any(|p| p.matches(class))
+    }
+}
+
+/// What the report groups spans by when no class or category is selected
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Class,
+    Category,
+}
+
+/// Order of the rows in the table
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Duration,
+    Name,
+}
+
+/// Whether to write colours and other escape codes
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Only when stdout is a terminal, unless NO_COLOR or CLICOLOR_FORCE say otherwise
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(Self::Auto),
+            "always" => Some(Self::Always),
+            "never" => Some(Self::Never),
+            _ => None,
+        }
+    }
+}
+
+/// How the report is printed, see export.rs for everything but the terminal
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Terminal,
+    Json,
+    Csv,
+    Tsv,
+    Markdown,
+}
+
+impl OutputFormat {
+    const ALL: [Self; 5] = [
+        Self::Terminal,
+        Self::Json,
+        Self::Csv,
+        Self::Tsv,
+        Self::Markdown,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Terminal => "terminal",
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Tsv => "tsv",
+            Self::Markdown => "markdown",
         }
     }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.name() == value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn preset_names_skip_flag_values() {
+        let args = args(&[
+            "@work", "--class", "@home", "-e", "@foo", "--preset", "weekly", "-d", "7",
+        ]);
+        assert_eq!(preset_names(&args), Some(vec!["work", "weekly"]));
+    }
+
+    #[test]
+    fn preset_names_need_a_name_after_preset() {
+        assert_eq!(preset_names(&args(&["-f", "--preset"])), None);
+    }
+
+    #[test]
+    fn preset_by_replaces_a_default_class() {
+        let mut settings = Settings::new(Config::without_file());
+        settings.apply(&ReportOptions {
+            class: Some("firefox".into()),
+            ..Default::default()
+        });
+        settings.apply(&ReportOptions {
+            by: Some(GroupBy::Category),
+            ..Default::default()
+        });
+        assert!(settings.class_arg.is_empty());
+
+        settings.apply(&ReportOptions {
+            class: Some("kitty".into()),
+            by: Some(GroupBy::Class),
+            ..Default::default()
+        });
+        assert_eq!(settings.class_arg, "kitty");
+    }
 }
@@ -0,0 +1,257 @@
+use crate::Settings;
+use crate::interval::Interval;
+use crate::log_parsing::{Span, compute_durations, key, parse_log};
+use crate::log_reader::LogReader;
+use crate::view::{Legend, collapse_tail, format_duration};
+use chrono::{DateTime, Local};
+use colored::Color;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// The size of a timeline image in pixels, the axis labels are drawn inside the height
+pub struct SvgOptions {
+    pub width: u32,
+    pub height: u32,
+    pub labels: bool,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            width: 960,
+            height: 58,
+            labels: true,
+        }
+    }
+}
+
+// the timeline is drawn on a dark background, like most terminals, so the palette looks the same
+pub const BACKGROUND: &str = "#18181b";
+pub const TRACK: &str = "#27272a";
+pub const DIMMED: &str = "#a1a1aa";
+const LABEL_HEIGHT: u32 = 18;
+// closer labels would overlap
+const MIN_LABEL_SPACING: f64 = 48.0;
+
+/// Writes the timeline of the report as an image, in the same colours as the terminal
+pub fn write_timeline(settings: &Settings, path: &Path, options: &SvgOptions) {
+    let mut reader = LogReader::new(settings);
+    let log = match parse_log(&mut reader, settings) {
+        Ok(log) => log,
+        Err(e) => {
+            eprintln!("Failed to parse log: {e:?}");
+            std::process::exit(1);
+        }
+    };
+    let (durations, total) = compute_durations(&log.spans, settings);
+    let (durations, groups) = collapse_tail(durations, total, settings);
+    let legend = Legend::new(&durations, &groups, settings);
+    let svg = timeline(
+        &log.spans,
+        &settings.interval,
+        settings,
+        &legend.colors,
+        options,
+    );
+    if let Err(e) = fs::write(path, svg) {
+        eprintln!("Failed to write {}: {e}", path.display());
+        std::process::exit(1);
+    }
+}
+
+/// Draws the spans inside `interval` at their exact position, each with a tooltip. Spans whose key
+/// isn't in `colors` are left out.
+pub fn timeline(
+    spans: &[Span],
+    interval: &Interval,
+    settings: &Settings,
+    colors: &HashMap<String, Color>,
+    options: &SvgOptions,
+) -> String {
+    let start = interval.start.timestamp_millis();
+    let end = interval.end.timestamp_millis();
+    let width = options.width;
+    let height = options.height;
+    let bar_height = match options.labels {
+        true => height.saturating_sub(LABEL_HEIGHT).max(1),
+        false => height,
+    };
+    let x = |ms: i64| (ms - start) as f64 * width as f64 / (end - start).max(1) as f64;
+
+    let mut svg = String::new();
+    writeln!(
+        &mut svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" font-family="sans-serif" font-size="11">"#
+    )
+    .unwrap();
+    writeln!(
+        &mut svg,
+        r#"<rect width="{width}" height="{height}" fill="{BACKGROUND}"/>"#
+    )
+    .unwrap();
+    writeln!(
+        &mut svg,
+        r#"<rect width="{width}" height="{bar_height}" fill="{TRACK}"/>"#
+    )
+    .unwrap();
+    svg.push_str("<g shape-rendering=\"crispEdges\">\n");
+    for span in spans {
+        let Some(color) = key(settings, &span.class, &span.title).and_then(|key| colors.get(&key))
+        else {
+            continue;
+        };
+        let (from, to) = (span.start.max(start), span.end.min(end));
+        if from >= to {
+            continue;
+        }
+        writeln!(
+            &mut svg,
+            r#"<rect x="{:.2}" width="{:.2}" height="{bar_height}" fill="{}"><title>{}</title></rect>"#,
+            x(from),
+            x(to) - x(from),
+            css_color(*color),
+            escape(&tooltip(span))
+        )
+        .unwrap();
+    }
+    svg.push_str("</g>\n");
+    if options.labels {
+        let ticks = ticks(interval);
+        // with many days only every few labels fit, the lines are still drawn for all of them
+        let step = (MIN_LABEL_SPACING * ticks.len() as f64 / width as f64).ceil() as usize;
+        for (i, (ms, label)) in ticks.into_iter().enumerate() {
+            let tick = x(ms);
+            writeln!(
+                &mut svg,
+                r#"<line x1="{tick:.2}" x2="{tick:.2}" y1="0" y2="{}" stroke="{DIMMED}" stroke-opacity="0.4"/>"#,
+                bar_height + 4
+            )
+            .unwrap();
+            // the last label may not fit after its tick
+            if i % step.max(1) == 0 && tick + MIN_LABEL_SPACING <= width as f64 {
+                writeln!(
+                    &mut svg,
+                    r#"<text x="{:.2}" y="{}" fill="{DIMMED}">{}</text>"#,
+                    tick + 2.0,
+                    height.saturating_sub(5),
+                    escape(&label)
+                )
+                .unwrap();
+            }
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// every 3 hours of local time for a single day, otherwise the start of each day
+fn ticks(interval: &Interval) -> Vec<(i64, String)> {
+    let days = interval.days();
+    if days.len() <= 1 {
+        return interval
+            .hours(3)
+            .into_iter()
+            .map(|(time, hour)| (time.timestamp_millis(), format!("{hour:02}:00")))
+            .collect();
+    }
+    days.iter()
+        .map(|day| {
+            let label = day.start.with_timezone(&Local).format("%a %d").to_string();
+            (day.start.timestamp_millis(), label)
+        })
+        .collect()
+}
+
+fn tooltip(span: &Span) -> String {
+    let time = |ms: i64, format: &str| {
+        DateTime::from_timestamp_millis(ms)
+            .map(|t| t.with_timezone(&Local).format(format).to_string())
+            .unwrap_or_else(|| ms.to_string())
+    };
+    format!(
+        "{}\n{}\n{} - {} ({})",
+        span.class,
+        span.title,
+        time(span.start, "%Y-%m-%d %H:%M:%S"),
+        time(span.end, "%H:%M:%S"),
+        format_duration((span.end - span.start) as u64)
+    )
+}
+
+/// The colour as a CSS hex colour, named colours use the xterm defaults
+pub fn css_color(color: Color) -> String {
+    let (r, g, b) = match color {
+        Color::Black => (0x00, 0x00, 0x00),
+        Color::Red => (0xcd, 0x00, 0x00),
+        Color::Green => (0x00, 0xcd, 0x00),
+        Color::Yellow => (0xcd, 0xcd, 0x00),
+        Color::Blue => (0x00, 0x00, 0xee),
+        Color::Magenta => (0xcd, 0x00, 0xcd),
+        Color::Cyan => (0x00, 0xcd, 0xcd),
+        Color::White => (0xe5, 0xe5, 0xe5),
+        Color::BrightBlack => (0x7f, 0x7f, 0x7f),
+        Color::BrightRed => (0xff, 0x00, 0x00),
+        Color::BrightGreen => (0x00, 0xff, 0x00),
+        Color::BrightYellow => (0xff, 0xff, 0x00),
+        Color::BrightBlue => (0x5c, 0x5c, 0xff),
+        Color::BrightMagenta => (0xff, 0x00, 0xff),
+        Color::BrightCyan => (0x00, 0xff, 0xff),
+        Color::BrightWhite => (0xff, 0xff, 0xff),
+        Color::TrueColor { r, g, b } => (r, g, b),
+    };
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Escapes text for XML, like SVG, HTML and the Pango markup of status bars
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_replaces_markup_characters() {
+        assert_eq!(
+            escape(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn escape_shows_entities_in_titles_as_written() {
+        // & is escaped first, so a title containing "&lt;" isn't shown as "<"
+        assert_eq!(escape("&lt;"), "&amp;lt;");
+    }
+
+    #[test]
+    fn labels_of_many_days_do_not_overlap() {
+        let settings = crate::Settings::new(crate::config::Config::without_file());
+        let options = SvgOptions::default();
+        let svg = timeline(
+            &[],
+            &Interval::from_day_count(30),
+            &settings,
+            &HashMap::new(),
+            &options,
+        );
+        let positions: Vec<f64> = svg
+            .lines()
+            .filter_map(|line| line.strip_prefix("<text x=\""))
+            .map(|line| line.split('"').next().unwrap().parse().unwrap())
+            .collect();
+        assert!(positions.len() > 1);
+        assert!(
+            positions
+                .windows(2)
+                .all(|pair| pair[1] - pair[0] >= MIN_LABEL_SPACING)
+        );
+    }
+}
@@ -0,0 +1,17 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// A fresh directory for this test run holding `files`, given as paths relative to it and their
+/// contents. Every test picks its own `name`, the caller removes the directory.
+pub fn temp_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join(format!("hyprlog-test-{}", std::process::id()))
+        .join(name);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, contents) in files {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+    dir
+}
@@ -1,33 +1,28 @@
-use crate::Settings;
-use crate::log_parsing::{compute_durations, timeline};
+use crate::config::Config;
+use crate::glyphs::Glyphs;
+use crate::interval::Interval;
+use crate::log_parsing::{
+    Anomaly, ParsedLog, Section, Span, compute_durations, ms_per_section, parse_log, timeline,
+};
 use crate::log_reader::LogReader;
+use crate::{ColorChoice, OutputFormat, Settings, export};
 use colored::{Color, Colorize};
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::env;
 use std::fmt::Write;
 use terminal_size::Width;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 pub fn render_log(settings: &Settings) {
     let mut reader = LogReader::new(settings);
-    if !reader.is_empty() {
-        match compute_durations(&mut reader, settings) {
-            Ok((durations, total)) => {
-                if durations.is_empty() {
-                    if &settings.class_arg == "" {
-                        println!("Empty log.");
-                    } else {
-                        println!("Class \"{}\" not found in log.", &settings.class_arg);
-                    }
-                    return;
-                }
-
-                let colors = key_to_color_map(&durations);
-                let labels: Vec<String> = durations.iter().map(|(s, _)| s.clone()).collect();
-                print_header(settings);
-                render_timelines(&mut reader, &colors, labels, settings);
-                print_table(durations, total, &colors);
-            }
+    // scripts get an empty report rather than a message
+    if !reader.is_empty() || settings.format != OutputFormat::Terminal {
+        match parse_log(&mut reader, settings) {
+            Ok(log) => render_parsed(settings, &log),
             Err(e) => {
-                eprintln!("Failed to compute durations: {e:?}");
+                eprintln!("Failed to parse log: {e:?}");
             }
         }
     } else {
@@ -38,196 +33,460 @@ pub fn render_log(settings: &Settings) {
     }
 }
 
-fn print_header(settings: &Settings) {
+/// Prints the report of a log that was already parsed
+pub fn render_parsed(settings: &Settings, log: &ParsedLog) {
+    if settings.format != OutputFormat::Terminal {
+        let (durations, total) = compute_durations(&log.spans, settings);
+        let (durations, groups) = collapse_tail(durations, total, settings);
+        export::print_report(settings, &durations, &groups, total, log);
+        return;
+    }
+    print!("{}", terminal_report(settings, log));
+}
+
+/// The report as it is drawn in the terminal, `hyprlog watch` keeps its parser around and draws
+/// this again whenever something changes
+pub fn terminal_report(settings: &Settings, log: &ParsedLog) -> String {
+    match settings.color {
+        ColorChoice::Always => colored::control::set_override(true),
+        ColorChoice::Never => colored::control::set_override(false),
+        // colored already checks for a terminal, NO_COLOR and CLICOLOR_FORCE
+        ColorChoice::Auto => {}
+    }
+    let mut out = String::new();
+    let (durations, total) = compute_durations(&log.spans, settings);
+    if durations.is_empty() {
+        if !settings.class_arg.is_empty() {
+            writeln!(out, "Class \"{}\" not found in log.", &settings.class_arg).unwrap();
+        } else if !settings.category_arg.is_empty() {
+            writeln!(
+                out,
+                "Category \"{}\" not found in log.",
+                &settings.category_arg
+            )
+            .unwrap();
+        } else {
+            writeln!(out, "Empty log.").unwrap();
+        }
+        return out;
+    }
+
+    let (durations, groups) = collapse_tail(durations, total, settings);
+    let legend = Legend::new(&durations, &groups, settings);
+    write_header(&mut out, settings);
+    render_timelines(&mut out, &log.spans, &legend, groups, settings);
+
+    let mut footer = Vec::new();
+    let unaccounted = log.unaccounted(settings);
+    if unaccounted > 0 {
+        footer.push(("Unaccounted", unaccounted));
+    }
+    if settings.show_inhibited {
+        footer.push(("Idle inhibited", log.inhibited_idle));
+    }
+    write_table(
+        &mut out,
+        durations,
+        total,
+        &legend,
+        &footer,
+        width(settings),
+    );
+    if settings.strict {
+        write_anomalies(&mut out, &log.anomalies);
+    }
+    out
+}
+
+// the corners, the horizontal and the vertical line around the date
+const ROUND_BOX: [char; 6] = ['╭', '╮', '╰', '╯', '─', '│'];
+const ASCII_BOX: [char; 6] = ['+', '+', '+', '+', '-', '|'];
+
+fn write_header(out: &mut String, settings: &Settings) {
     let date_str = settings.interval.date_str();
-    let term_width = terminal_width();
+    let term_width = width(settings);
 
-    let inner_width = date_str.len() + 2;
+    let inner_width = date_str.width() + 2;
     let box_width = inner_width + 2;
 
     if box_width > term_width {
-        println!("{}", date_str);
+        writeln!(out, "{}", date_str).unwrap();
         return;
     }
 
     let start_column = (term_width - box_width) / 2;
     let pad = " ".repeat(start_column);
 
-    println!("{}╭{}╮", pad, "─".repeat(inner_width));
-    println!("{}│ {} │", pad, date_str);
-    println!("{}╰{}╯", pad, "─".repeat(inner_width));
-    println!("\n");
+    let box_chars = if settings.glyphs.is_ascii() {
+        ASCII_BOX
+    } else {
+        ROUND_BOX
+    };
+    let [top_left, top_right, bottom_left, bottom_right, line, side] = box_chars;
+    let line = line.to_string().repeat(inner_width);
+
+    writeln!(out, "{pad}{top_left}{line}{top_right}").unwrap();
+    writeln!(out, "{pad}{side} {date_str} {side}").unwrap();
+    writeln!(out, "{pad}{bottom_left}{line}{bottom_right}").unwrap();
+    writeln!(out, "\n").unwrap();
 }
 
 const STRIKE_ON: &str = "\x1b[9m";
 const STRIKE_OFF: &str = "\x1b[29m";
-const FANCY_TIMELINE: bool = true;
-const CUTOFF: usize = usize::MAX; // not doing anything but the setting is here
+const OTHER: &str = "Other";
+const OTHER_COLOR: Color = Color::White;
+
+/// How each row is drawn, in the timeline and the table
+pub struct Legend {
+    pub colors: HashMap<String, Color>,
+    /// Letters for --patterns, empty otherwise
+    markers: HashMap<String, char>,
+}
+
+impl Legend {
+    /// `groups` are the keys each row stands for, as returned by `collapse_tail`
+    pub fn new(rows: &[(String, u64)], groups: &[Vec<String>], settings: &Settings) -> Self {
+        // the "Other" row is the one that stands for several keys
+        let other = rows.iter().zip(groups).find(|(_, keys)| keys.len() > 1);
+        let other_key = other.map(|((key, _), _)| key.as_str());
+        let mut colors = key_to_color_map(rows, other_key, &settings.config);
+        let mut markers = match settings.patterns {
+            true => key_to_marker_map(rows),
+            false => HashMap::new(),
+        };
+        // folded keys are drawn like the "Other" row
+        if let Some(((other, _), keys)) = other {
+            let color = colors[other];
+            let marker = markers.get(other).copied();
+            for key in keys {
+                colors.insert(key.clone(), color);
+                if let Some(marker) = marker {
+                    markers.insert(key.clone(), marker);
+                }
+            }
+        }
+        Self { colors, markers }
+    }
+}
+
+const DAY_LABEL_WIDTH: usize = 15; // "Mon 2025-08-04 "
 
 pub fn render_timelines(
-    reader: &mut LogReader,
-    colors: &HashMap<String, Color>,
-    labels: Vec<String>,
+    out: &mut String,
+    spans: &[Span],
+    legend: &Legend,
+    groups: Vec<Vec<String>>,
     settings: &Settings,
 ) {
     if !settings.multi_timeline {
-        render_timeline(reader, colors, settings, None);
+        render_timeline(out, spans, legend, settings, None);
     } else {
-        let mut count = 0;
-        for label in labels {
-            if label.len() == 0 {
-                continue;
-            }
-            if count >= CUTOFF {
-                break;
-            }
-            render_timeline(reader, colors, settings, Some(&label));
-            count += 1;
+        for keys in groups
+            .iter()
+            .filter(|keys| keys.iter().any(|k| !k.is_empty()))
+        {
+            render_timeline(out, spans, legend, settings, Some(keys));
         }
     }
 }
 
 pub fn render_timeline(
-    reader: &mut LogReader,
-    colors: &HashMap<String, Color>,
+    out: &mut String,
+    spans: &[Span],
+    legend: &Legend,
+    settings: &Settings,
+    label: Option<&[String]>,
+) {
+    let days = settings.interval.days();
+    if settings.stacked && days.len() > 1 {
+        render_stacked_timeline(out, spans, legend, settings, label, &days);
+        return;
+    }
+
+    let width = width(settings);
+    let sections = timeline(spans, width, &settings.interval, settings, label);
+    let ms_per_section = ms_per_section(&settings.interval, width);
+    writeln!(
+        out,
+        "{}\n",
+        timeline_string(sections, ms_per_section, legend, settings, label)
+    )
+    .unwrap();
+}
+
+// one row per day on a shared 24h axis, so days can be compared against each other
+fn render_stacked_timeline(
+    out: &mut String,
+    spans: &[Span],
+    legend: &Legend,
     settings: &Settings,
-    label: Option<&String>,
+    label: Option<&[String]>,
+    days: &[Interval],
 ) {
-    let width = terminal_width();
-    let sections = timeline(reader, width, &settings, label);
+    let width = width(settings).saturating_sub(DAY_LABEL_WIDTH).max(1);
+    let mut axis = None;
+    for day in days {
+        // days with a DST change are longer or shorter, they get their own axis
+        let day_axis = hour_axis(day, width);
+        if axis.as_ref() != Some(&day_axis) {
+            writeln!(out, "{}{}", " ".repeat(DAY_LABEL_WIDTH), day_axis).unwrap();
+            axis = Some(day_axis);
+        }
+        let sections = timeline(spans, width, day, settings, label);
+        let ms_per_section = ms_per_section(day, width);
+        writeln!(
+            out,
+            "{:<label_width$}{}",
+            day.day_label(),
+            timeline_string(sections, ms_per_section, legend, settings, label),
+            label_width = DAY_LABEL_WIDTH
+        )
+        .unwrap();
+    }
+    writeln!(out).unwrap();
+}
+
+fn hour_axis(day: &Interval, width: usize) -> String {
+    let step = if width >= 96 { 3 } else { 6 };
+    let start = day.start.timestamp_millis();
+    let mut axis = vec![' '; width];
+    for (time, hour) in day.hours(step) {
+        let position =
+            ((time.timestamp_millis() - start) * width as i64 / day.width() as i64) as usize;
+        let mark = format!("{hour:02}");
+        if position + mark.len() > width {
+            break;
+        }
+        for (i, ch) in mark.chars().enumerate() {
+            axis[position + i] = ch;
+        }
+    }
+    axis.into_iter().collect::<String>().dimmed().to_string()
+}
+
+fn timeline_string(
+    sections: Vec<Section>,
+    ms_per_section: i64,
+    legend: &Legend,
+    settings: &Settings,
+    label: Option<&[String]>,
+) -> String {
     let mut timeline_string = String::from("");
 
     for section_data in sections {
         let key = match settings.multi_timeline {
             false => &section_data.0,
-            true => label.unwrap(),
+            true => &label.unwrap()[0],
         };
-        if let Some(color) = colors.get(key) {
-            let ch = choose_character(section_data, settings).to_string();
-            let glyph = if *color == Color::Black {
-                if FANCY_TIMELINE {
-                    " ".strikethrough().bold().white().to_string()
-                } else {
-                    "—".white().to_string()
-                }
+        if let Some(color) = legend.colors.get(key) {
+            let idle = key.is_empty();
+            let glyphs = &settings.glyphs;
+            let marker = legend.markers.get(key);
+            let ch = match marker {
+                Some(marker) => choose_marker(*marker, section_data, ms_per_section),
+                None => choose_character(section_data, ms_per_section, glyphs),
+            }
+            .to_string();
+            let glyph = if idle && glyphs.strike {
+                glyphs
+                    .idle
+                    .to_string()
+                    .strikethrough()
+                    .bold()
+                    .white()
+                    .to_string()
+            } else if idle {
+                glyphs.idle.to_string().white().to_string()
             } else {
                 format!("{}", ch.color(*color))
             };
 
-            write!(&mut timeline_string, "{}{}{}", STRIKE_ON, glyph, STRIKE_OFF).unwrap();
+            // a line through the markers would make them hard to read, and the escape codes are
+            // only wanted along with colours
+            let strike = glyphs.strike && (idle || marker.is_none());
+            if strike && colored::control::SHOULD_COLORIZE.should_colorize() {
+                write!(&mut timeline_string, "{}{}{}", STRIKE_ON, glyph, STRIKE_OFF).unwrap();
+            } else {
+                timeline_string.push_str(&glyph);
+            }
         }
     }
-    println!("{timeline_string}\n");
+    timeline_string
 }
 
-fn choose_character(section_data: (String, i64, i64, bool, bool), settings: &Settings) -> char {
-    let width = terminal_width();
-    let ms_per_section = (settings.interval.width() / (width as u64)) as f64;
+fn choose_character(section_data: Section, ms_per_section: i64, glyphs: &Glyphs) -> char {
     let fullness = section_data.2 as f64 / ms_per_section as f64;
-    if FANCY_TIMELINE {
-        if section_data.3 && section_data.4 {
-            // there is activity near both the left and right side of a section
-            return '█';
-        } else if section_data.3 {
-            // there is activity near the left side of a section
-            return match fullness {
-                f64::MIN..=0.00 => ' ',
-                0.0..=0.1250000 => '▏',
-                0.125..=0.25000 => '▎',
-                0.25..=0.375000 => '▍',
-                0.375..=0.50000 => '▌',
-                0.5..=0.6250000 => '▋',
-                0.625..=0.75000 => '▊',
-                0.75..=f64::MAX => '█',
-                _ => '—',
-            };
-        } else if section_data.4 {
-            // there is activity near the right side of a section
-            return match fullness {
-                f64::MIN..=0.00 => ' ',
-                0.0..=0.1250000 => '🮇',
-                0.125..=0.25000 => '🮈',
-                0.25..=0.375000 => '▐',
-                0.375..=0.50000 => '🮉',
-                0.5..=0.6250000 => '🮊',
-                0.625..=0.75000 => '🮋',
-                0.75..=f64::MAX => '█',
-                _ => ' ',
-            };
-        } else {
-            return match fullness {
-                f64::MIN..=0.00 => ' ',
-                0.00..=0.333333 => '│',
-                0.333333..=0.66 => '┃',
-                0.66..=f64::MAX => '█',
-                _ => ' ',
-            };
-        }
-    } else {
-        if fullness == 0.0 {
-            return ' ';
-        }
-        return '█';
-    }
+    glyphs.choose(fullness, section_data.3, section_data.4)
 }
 
-fn print_table(rows: Vec<(String, u64)>, total: u64, colors: &HashMap<String, Color>) {
-    let mut max_class_width = rows.iter().map(|(class, _)| class.len()).max().unwrap_or(0);
+// the row's letter, lowercase when less than half of the section is active
+fn choose_marker(marker: char, section_data: Section, ms_per_section: i64) -> char {
+    let fullness = section_data.2 as f64 / ms_per_section as f64;
+    match fullness {
+        f if f <= 0.0 => ' ',
+        f if f < 0.5 => marker.to_ascii_lowercase(),
+        _ => marker,
+    }
+}
 
-    let max_string_length = terminal_width() - 20;
+// footer rows are printed below the total, they aren't part of it so they have no percentage
+fn write_table(
+    out: &mut String,
+    rows: Vec<(String, u64)>,
+    total: u64,
+    legend: &Legend,
+    footer: &[(&str, u64)],
+    width: usize,
+) {
+    let mut max_class_width = rows
+        .iter()
+        .map(|(class, _)| class.width())
+        .chain(footer.iter().map(|(name, _)| name.width()))
+        .chain(["Total".width()])
+        .max()
+        .unwrap();
+
+    // multi day totals like "1d 11:31:00" are wider than the usual 10 columns
+    let rows_total: u64 = rows.iter().map(|(_, duration)| duration).sum();
+    let duration_width = footer
+        .iter()
+        .map(|(_, duration)| format_duration(*duration).width())
+        .chain([format_duration(rows_total).width(), 10])
+        .max()
+        .unwrap();
+
+    // the markers are the legend for --patterns timelines
+    let marker_width = if legend.markers.is_empty() { 0 } else { 2 };
+
+    let max_string_length = width
+        .saturating_sub(marker_width + duration_width + 10)
+        .max(1);
     max_class_width = max_class_width.min(max_string_length);
+    let total_width = marker_width + max_class_width + duration_width + 8 + 2; // +2 for the spaces between columns
+    let left_padding = width.saturating_sub(total_width) / 2;
 
-    let total_width = max_class_width + 10 + 8 + 2; // +2 for the spaces between columns
-    let left_padding = (terminal_width() - total_width) / 2;
-
-    println!("");
+    writeln!(out).unwrap();
 
     let mut total_percentage = 0.0;
     let mut total_duration = 0;
-    let mut count = 0;
     for (class, duration) in rows {
-        if count >= CUTOFF {
-            break;
-        }
         total_duration += duration;
         let percent = 100.0 * (duration as f64 / total as f64);
         total_percentage += percent;
-        let color = colors.get(&class).unwrap();
-        println!(
-            "{}{:<width$} {:>10} {:>7.2}%",
+        let color = legend.colors.get(&class).unwrap();
+        let marker = match legend.markers.get(&class) {
+            Some(marker) => format!("{} ", marker.to_string().color(*color).bold()),
+            None => " ".repeat(marker_width),
+        };
+        writeln!(
+            out,
+            "{}{}{} {:>duration_width$} {:>7.2}%",
             " ".repeat(left_padding),
-            truncate_string(&class, max_string_length).color(*color),
+            marker,
+            pad_string(&class, max_class_width).color(*color),
             format_duration(duration),
             percent,
-            width = max_class_width
-        );
-        count += 1;
+        )
+        .unwrap();
     }
 
-    println!(
+    writeln!(
+        out,
         "{}",
         format!(
-            "\n{}{:<width$} {:>10} {:>7.2}%",
-            " ".repeat(left_padding),
-            truncate_string(&"Total", max_string_length),
+            "\n{}{} {:>duration_width$} {:>7.2}%",
+            " ".repeat(left_padding + marker_width),
+            pad_string("Total", max_class_width),
             format_duration(total_duration),
             total_percentage,
-            width = max_class_width
         )
         .bold()
-    );
+    )
+    .unwrap();
+
+    for (name, duration) in footer {
+        writeln!(
+            out,
+            "{}",
+            format!(
+                "{}{} {:>duration_width$}",
+                " ".repeat(left_padding + marker_width),
+                pad_string(name, max_class_width),
+                format_duration(*duration),
+            )
+            .dimmed()
+        )
+        .unwrap();
+    }
+}
+
+// strict mode doesn't guess, so show the user what it refused to guess about
+fn write_anomalies(out: &mut String, anomalies: &[Anomaly]) {
+    if anomalies.is_empty() {
+        return;
+    }
+    writeln!(out, "\n{}", "Anomalies".bold()).unwrap();
+    for anomaly in anomalies {
+        writeln!(
+            out,
+            "{}  {}",
+            anomaly_time(anomaly).dimmed(),
+            anomaly.description.yellow()
+        )
+        .unwrap();
+    }
 }
 
-fn truncate_string(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
-        s.to_string()
-    } else if max_len > 3 {
-        format!("{}...", &s[..max_len - 3])
+/// When the anomaly happened in local time, or the raw timestamp if it's out of range
+pub fn anomaly_time(anomaly: &Anomaly) -> String {
+    chrono::DateTime::from_timestamp_millis(anomaly.timestamp)
+        .map(|t| {
+            t.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_else(|| anomaly.timestamp.to_string())
+}
+
+/// Shortens `s` to at most `max_width` terminal columns without splitting a character. Paths keep
+/// their end, the file name is usually the interesting part, so they lose their middle instead.
+fn truncate_string(s: &str, max_width: usize) -> String {
+    if s.width() <= max_width {
+        return s.to_string();
+    }
+    if max_width <= 3 {
+        return ".".repeat(max_width); // handles silly small max_width
+    }
+    let budget = max_width - 3;
+    if s.contains('/') {
+        let end = budget / 2;
+        let start = take_width(s.graphemes(true), budget - end);
+        let mut tail = take_width(s.graphemes(true).rev(), end);
+        tail.reverse();
+        format!("{}...{}", start.concat(), tail.concat())
     } else {
-        ".".repeat(max_len) // handles silly small max_len
+        format!("{}...", take_width(s.graphemes(true), budget).concat())
     }
 }
 
+// graphemes from the iterator until they would take more than `width` columns
+fn take_width<'a>(graphemes: impl Iterator<Item = &'a str>, width: usize) -> Vec<&'a str> {
+    let mut used = 0;
+    graphemes
+        .take_while(|g| {
+            used += g.width();
+            used <= width
+        })
+        .collect()
+}
+
+// `format!("{:<width$}")` counts chars, this counts columns so wide characters stay aligned
+fn pad_string(s: &str, width: usize) -> String {
+    let s = truncate_string(s, width);
+    let padding = width.saturating_sub(s.width());
+    format!("{}{}", s, " ".repeat(padding))
+}
+
 pub fn format_duration(ms: u64) -> String {
     if ms < 1_000 {
         return format!("{ms}ms");
@@ -266,41 +525,301 @@ pub fn format_duration(ms: u64) -> String {
     }
 }
 
-fn terminal_width() -> usize {
-    return match terminal_size::terminal_size() {
+/// `--width`, or the width of the terminal. When stdout isn't a terminal this falls back to
+/// `$COLUMNS`, and then to 80.
+fn width(settings: &Settings) -> usize {
+    if let Some(width) = settings.width {
+        return width;
+    }
+    match terminal_size::terminal_size() {
         Some((Width(w), _)) => w as usize,
-        None => 120,
-    };
+        None => env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse().ok())
+            .filter(|columns| *columns > 0)
+            .unwrap_or(80),
+    }
+}
+
+/// Fold the rows past `--top` or below `--min-percent` into one "Other (N)" row, N being the number
+/// of rows it stands for. Returns the rows and, for each row, the keys it stands for.
+pub fn collapse_tail(
+    rows: Vec<(String, u64)>,
+    total: u64,
+    settings: &Settings,
+) -> (Vec<(String, u64)>, Vec<Vec<String>>) {
+    let (mut rows, folded) = split_tail(rows, total, settings);
+    let mut groups: Vec<Vec<String>> = rows.iter().map(|(key, _)| vec![key.clone()]).collect();
+    // an "Other" row that only hides one entry isn't saving any space
+    if folded.len() == 1 {
+        let row = folded.into_iter().next().unwrap();
+        groups.push(vec![row.0.clone()]);
+        rows.push(row);
+    } else if !folded.is_empty() {
+        let duration = folded.iter().map(|(_, duration)| duration).sum();
+        rows.push((other_row_name(folded.len()), duration));
+        groups.push(folded.into_iter().map(|(key, _)| key).collect());
+    }
+    (rows, groups)
 }
 
-pub fn color_from_index(index: usize) -> Color {
-    return match index {
-        0 => Color::Green,
-        1 => Color::Red,
-        2 => Color::Blue,
-        3 => Color::Magenta,
-        4 => Color::Yellow,
-        5 => Color::Cyan,
-        6 => Color::BrightRed,
-        7 => Color::BrightGreen,
-        8 => Color::BrightBlue,
-        9 => Color::BrightMagenta,
-        10 => Color::BrightYellow,
-        11 => Color::BrightCyan,
-        _ => Color::White,
+/// Rows of the table, each key with its duration
+type Rows = Vec<(String, u64)>;
+
+/// The rows `--top` and `--min-percent` keep, in their order, and the rows they fold
+pub fn split_tail(rows: Rows, total: u64, settings: &Settings) -> (Rows, Rows) {
+    let keep = |index: usize, duration: u64| {
+        let percent = 100.0 * (duration as f64 / total as f64);
+        // without any active time there are no percentages to compare
+        settings.top.is_none_or(|top| index < top)
+            && (total == 0 || settings.min_percent.is_none_or(|min| percent >= min))
     };
+    // the longest rows are kept, whatever order they are shown in
+    let mut by_duration: Vec<usize> = (0..rows.len()).collect();
+    by_duration.sort_by_key(|index| Reverse(rows[*index].1));
+    let mut kept = vec![false; rows.len()];
+    for (rank, index) in by_duration.into_iter().enumerate() {
+        kept[index] = keep(rank, rows[index].1);
+    }
+    let (kept, folded): (Vec<_>, Vec<_>) = rows.into_iter().zip(kept).partition(|(_, kept)| *kept);
+    (
+        kept.into_iter().map(|(row, _)| row).collect(),
+        folded.into_iter().map(|(row, _)| row).collect(),
+    )
+}
+
+// the count keeps it apart from a class or category that is really called Other
+pub fn other_row_name(count: usize) -> String {
+    format!("{OTHER} ({count})")
+}
+
+/// A letter for each row, for telling rows apart without colour. Past Z the digits are used. That
+/// is 35 rows, every row after those shares '+', use --top to fold them into the Other row instead.
+fn key_to_marker_map(list: &[(String, u64)]) -> HashMap<String, char> {
+    let mut markers = ('A'..='Z').chain('1'..='9');
+    list.iter()
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, _)| (key.clone(), markers.next().unwrap_or('+')))
+        .collect()
 }
 
-fn key_to_color_map(list: &Vec<(String, u64)>) -> HashMap<String, Color> {
+/// Each key prefers the palette colour picked by a hash of its name, so it usually keeps its
+/// colour across days and reports. When two visible keys want the same colour, the one first in
+/// alphabetical order gets it and the other moves on to the next free colour, so up to a palette's
+/// worth of rows always get distinct colours, whatever their durations. Colours set with `color`
+/// in the config always win.
+fn key_to_color_map(
+    list: &[(String, u64)],
+    other: Option<&str>,
+    config: &Config,
+) -> HashMap<String, Color> {
     let mut res: HashMap<String, Color> = HashMap::new();
     res.insert(String::from(""), Color::Black);
-    let mut color_index = 0;
-    for entry in list {
-        if !res.contains_key(&entry.0) {
-            res.insert(entry.0.clone(), color_from_index(color_index));
-            color_index += 1;
+    let palette = &config.palette;
+    let mut used = vec![false; palette.len()];
+    for (key, _) in list {
+        // the "Other" row takes the colour set for Other, whatever it hides
+        let is_other = other == Some(key.as_str());
+        let color = match config.colors.get(if is_other { OTHER } else { key }) {
+            Some(color) => *color,
+            None if is_other => OTHER_COLOR,
+            None => continue,
+        };
+        if let Some(index) = palette.iter().position(|c| *c == color) {
+            used[index] = true;
         }
+        res.insert(key.clone(), color);
+    }
+
+    let mut keys: Vec<&String> = list
+        .iter()
+        .map(|(key, _)| key)
+        .filter(|key| !res.contains_key(*key))
+        .collect();
+    keys.sort();
+    for key in keys {
+        let start = (hash(key) % palette.len() as u64) as usize;
+        let index = (0..palette.len())
+            .map(|offset| (start + offset) % palette.len())
+            .find(|index| !used[*index])
+            .unwrap_or(start);
+        used[index] = true;
+        res.insert(key.clone(), palette[index]);
+    }
+    res
+}
+
+// FNV-1a, unlike std's hasher it's guaranteed to stay the same between builds
+fn hash(key: &str) -> u64 {
+    key.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SortOrder;
+
+    fn rows(rows: &[(&str, u64)]) -> Vec<(String, u64)> {
+        rows.iter().map(|(key, d)| (key.to_string(), *d)).collect()
     }
 
-    return res;
+    #[test]
+    fn collapse_tail_keeps_the_longest_rows_when_sorted_by_name() {
+        let mut settings = Settings::new(Config::without_file());
+        settings.sort = SortOrder::Name;
+        settings.top = Some(2);
+        let durations = rows(&[("a", 1), ("b", 50), ("c", 2), ("d", 40)]);
+
+        let (rows, groups) = collapse_tail(durations, 93, &settings);
+        assert_eq!(
+            rows,
+            [("b".into(), 50), ("d".into(), 40), ("Other (2)".into(), 3)]
+        );
+        assert_eq!(groups[2], ["a", "c"]);
+    }
+
+    #[test]
+    fn collapse_tail_applies_min_percent_by_duration() {
+        let mut settings = Settings::new(Config::without_file());
+        settings.sort = SortOrder::Name;
+        settings.min_percent = Some(10.0);
+        let durations = rows(&[("a", 5), ("b", 50), ("c", 5), ("d", 40)]);
+
+        let (rows, _) = collapse_tail(durations, 100, &settings);
+        assert_eq!(
+            rows,
+            [("b".into(), 50), ("d".into(), 40), ("Other (2)".into(), 10)]
+        );
+    }
+
+    #[test]
+    fn key_colors_are_distinct_and_stable() {
+        let config = Config::without_file();
+        // a palette's worth of rows, including keys whose hashes collide
+        let mut keys: Vec<String> = ["code", "nautilus", "chromium", "foot"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        keys.extend((keys.len()..config.palette.len()).map(|i| format!("class{i}")));
+        let list: Vec<(&str, u64)> = keys.iter().map(|key| (key.as_str(), 1)).collect();
+        let colors = key_to_color_map(&rows(&list), None, &config);
+        for (i, a) in keys.iter().enumerate() {
+            for b in &keys[i + 1..] {
+                assert_ne!(colors[a], colors[b], "{a} and {b}");
+            }
+        }
+
+        // the order and durations of the rows don't matter
+        let mut reversed = list.clone();
+        reversed.reverse();
+        reversed[0].1 = 100;
+        assert_eq!(key_to_color_map(&rows(&reversed), None, &config), colors);
+
+        // a key without competition gets the colour it prefers
+        let alone = key_to_color_map(&rows(&[("kitty", 1)]), None, &config);
+        let preferred = (hash("kitty") % config.palette.len() as u64) as usize;
+        assert_eq!(alone["kitty"], config.palette[preferred]);
+    }
+
+    #[test]
+    fn table_rows_fit_the_width_with_markers() {
+        colored::control::set_override(false);
+        let mut settings = Settings::new(Config::without_file());
+        settings.patterns = true;
+        let durations = rows(&[
+            ("a-class-name-much-longer-than-the-table", 60_000),
+            ("kitty", 1_000),
+        ]);
+        let groups: Vec<Vec<String>> = durations.iter().map(|(key, _)| vec![key.clone()]).collect();
+        let legend = Legend::new(&durations, &groups, &settings);
+
+        let mut out = String::new();
+        write_table(
+            &mut out,
+            durations,
+            61_000,
+            &legend,
+            &[("Unaccounted", 5)],
+            40,
+        );
+        for line in out.lines() {
+            assert!(line.width() <= 40, "{line:?}");
+        }
+        assert!(out.lines().any(|line| line.width() == 40));
+    }
+
+    #[test]
+    fn total_label_is_not_cut_for_short_keys() {
+        colored::control::set_override(false);
+        let settings = Settings::new(Config::without_file());
+        let durations = rows(&[("nvim", 60_000), ("zsh", 1_000)]);
+        let groups: Vec<Vec<String>> = durations.iter().map(|(key, _)| vec![key.clone()]).collect();
+        let legend = Legend::new(&durations, &groups, &settings);
+
+        let mut out = String::new();
+        write_table(&mut out, durations, 61_000, &legend, &[], 80);
+        let total = out.lines().find(|line| line.contains("01:01")).unwrap();
+        assert_eq!(total.split_whitespace().next(), Some("Total"), "{total}");
+        // the keys are padded to the label's width
+        let zsh = out.lines().find(|line| line.contains("zsh")).unwrap();
+        assert_eq!(zsh.find("00:01"), total.find("01:01"));
+    }
+
+    #[test]
+    fn collapse_tail_keeps_everything_without_active_time() {
+        let mut settings = Settings::new(Config::without_file());
+        settings.min_percent = Some(10.0);
+        let durations = rows(&[("a", 0), ("b", 0)]);
+
+        let (rows, _) = collapse_tail(durations.clone(), 0, &settings);
+        assert_eq!(rows, durations);
+    }
+
+    #[test]
+    fn truncate_string_counts_columns_and_keeps_graphemes_whole() {
+        #[rustfmt::skip]
+        let cases = [
+            ("kitty", 5, "kitty"),
+            ("kitty terminal", 8, "kitty..."),
+            // wide characters take two columns, one that doesn't fit leaves a column unused
+            ("日本語テキスト", 8, "日本..."),
+            ("日本語テキスト", 9, "日本語..."),
+            // an emoji with a skin tone and a flag are one grapheme each
+            ("👍🏽👍🏽👍🏽x", 6, "👍🏽..."),
+            ("🇩🇪🇩🇪🇩🇪", 5, "🇩🇪..."),
+            // combining accents stay with their letter
+            ("e\u{301}e\u{301}e\u{301}e\u{301}e\u{301}", 4, "e\u{301}..."),
+            // paths lose their middle instead of the file name
+            ("/home/user/projects/hyprlog/src/main.rs", 20, "/home/use.../main.rs"),
+            ("~/写真/旅行/写真.png", 12, "~/写....png"),
+            ("kitty", 3, "..."),
+            ("kitty", 1, "."),
+            ("kitty", 0, ""),
+            ("日本", 3, "..."),
+        ];
+        for (s, max_width, expected) in cases {
+            let truncated = truncate_string(s, max_width);
+            assert_eq!(truncated, expected, "{s:?} in {max_width}");
+            assert!(truncated.width() <= max_width);
+        }
+    }
+
+    #[test]
+    fn pad_string_fills_the_width_in_columns() {
+        #[rustfmt::skip]
+        let cases = [
+            ("kitty", 8, "kitty   "),
+            ("日本", 6, "日本  "),
+            ("日本語テキスト", 8, "日本... "),
+            ("e\u{301}", 3, "e\u{301}  "),
+            ("kitty", 2, ".."),
+        ];
+        for (s, width, expected) in cases {
+            let padded = pad_string(s, width);
+            assert_eq!(padded, expected, "{s:?} in {width}");
+            assert_eq!(padded.width(), width);
+        }
+    }
 }
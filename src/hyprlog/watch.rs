@@ -0,0 +1,111 @@
+use crate::Settings;
+use crate::interval::Interval;
+use crate::log_parsing::SpanParser;
+use crate::log_reader::LogTail;
+use crate::view::terminal_report;
+use chrono::Utc;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use terminal_size::Height;
+
+// switch to the alternate screen and hide the cursor, and back
+const ENTER: &str = "\x1b[?1049h\x1b[?25l";
+const LEAVE: &str = "\x1b[?25h\x1b[?1049l";
+// terminals that support synchronized output show a frame all at once instead of line by line
+const BEGIN_FRAME: &str = "\x1b[?2026h\x1b[H\x1b[2J";
+const END_FRAME: &str = "\x1b[?2026l";
+// how often the day files are checked, resizes are noticed within a tick
+const TICK: Duration = Duration::from_millis(100);
+const POLL: Duration = Duration::from_secs(1);
+
+static QUIT: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn quit(_: libc::c_int) {
+    QUIT.store(true, Ordering::Relaxed);
+}
+
+/// Draws the report in the alternate screen until interrupted. Only rows added to the day files
+/// since the last refresh are parsed, the parser keeps its state in between.
+pub fn watch(settings: &mut Settings) {
+    // the escapes below would only garble a file or pipe
+    if !io::stdout().is_terminal() {
+        eprintln!("hyprlog watch needs a terminal, use --format to get a report for scripts.");
+        std::process::exit(1);
+    }
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            quit as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+        libc::signal(
+            libc::SIGTERM,
+            quit as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+    // a relative interval moves along at midnight
+    let days = settings.interval.days().len() as u64;
+    let relative = settings.interval == Interval::from_day_count(days);
+
+    let mut tail = LogTail::new(settings);
+    let mut parser = SpanParser::default();
+    let mut error = None;
+    let mut size = terminal_size::terminal_size();
+    let mut polled: Option<Instant> = None;
+    print!("{ENTER}");
+
+    while !QUIT.load(Ordering::Relaxed) {
+        let mut redraw = false;
+        if relative && settings.interval != Interval::from_day_count(days) {
+            settings.interval = Interval::from_day_count(days);
+            tail = LogTail::new(settings);
+            parser = SpanParser::default();
+        }
+        if polled.is_none_or(|t| t.elapsed() >= POLL) {
+            polled = Some(Instant::now());
+            // the open span keeps growing, so this redraws at least once a second
+            redraw = true;
+            let (records, mut errors) = tail.read_new();
+            for record in records {
+                if let Ok(timestamp) = record[0].parse() {
+                    parser.push(settings, timestamp, &record[1], &record[2]);
+                }
+            }
+            // skipped rows aren't read again, so the last problem stays on screen
+            if let Some(e) = errors.pop() {
+                error = Some(e);
+            }
+        }
+        let new_size = terminal_size::terminal_size();
+        if new_size != size {
+            size = new_size;
+            redraw = true;
+        }
+
+        if redraw {
+            let report = terminal_report(
+                settings,
+                &parser.parsed(settings, Utc::now().timestamp_millis()),
+            );
+            let mut lines: Vec<String> = report.lines().map(String::from).collect();
+            if let Some(e) = &error {
+                lines.extend([String::new(), format!("Failed to read log: {e:#}")]);
+            }
+            // whatever doesn't fit would scroll the top of the report away
+            if let Some((_, Height(height))) = size {
+                let height = height as usize;
+                if lines.len() > height {
+                    let error_lines = if error.is_some() { 2 } else { 0 };
+                    lines.drain(height.saturating_sub(error_lines)..lines.len() - error_lines);
+                }
+            }
+            print!("{BEGIN_FRAME}{}{END_FRAME}", lines.join("\n"));
+            let _ = io::stdout().flush();
+        }
+        thread::sleep(TICK);
+    }
+    print!("{LEAVE}");
+    let _ = io::stdout().flush();
+}
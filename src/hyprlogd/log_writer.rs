@@ -4,11 +4,12 @@ use std::{
     fs::{File, OpenOptions, create_dir_all},
     io::{self, Write},
     os::unix::net::UnixDatagram,
-    path::PathBuf,
+    path::{Path, PathBuf},
     time::Duration,
 };
 
 use crate::Settings;
+use crate::sockets::SNITCH_SOCKET;
 pub enum LogMsg {
     Line {
         ts: i64,
@@ -38,7 +39,7 @@ impl LogWriter {
         })
     }
 
-    fn today_path(base_dir: &PathBuf) -> ((i32, u32, u32), PathBuf) {
+    fn today_path(base_dir: &Path) -> ((i32, u32, u32), PathBuf) {
         let now = Utc::now();
         let day_key = (now.year(), now.month(), now.day());
         let path = base_dir.join(format!("{}", now.format("%Y-%m-%d.csv")));
@@ -67,7 +68,7 @@ impl LogWriter {
     }
 
     fn create_log_file(path: &PathBuf) -> io::Result<File> {
-        let mut f = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut f = OpenOptions::new().create(true).append(true).open(path)?;
         if f.metadata()?.len() == 0 {
             f.write_all(b"timestamp,class,title\n")?;
         }
@@ -142,8 +143,7 @@ pub fn log_error<S: AsRef<str>>(text: S) {
 }
 
 fn send_datagram(msg: &str) {
-    const SOCKET_PATH: &str = "/tmp/hyprlog-snitch.sock";
     if let Ok(sock) = UnixDatagram::unbound() {
-        let _ = sock.send_to(msg.as_bytes(), SOCKET_PATH);
+        let _ = sock.send_to(msg.as_bytes(), SNITCH_SOCKET);
     }
 }
@@ -1,6 +1,8 @@
 mod log_writer;
 mod shutdown;
 mod socket;
+#[path = "../sockets.rs"]
+mod sockets;
 
 use std::{env, time::Duration};
 
@@ -15,10 +17,9 @@ async fn main() -> hyprland::Result<()> {
     let mut settings = Settings::new();
 
     let args: Vec<String> = env::args().collect();
-    match args.get(1).map(String::as_str) {
-        Some("snitch") => settings.snitch = true,
-        _ => {}
-    };
+    if let Some("snitch") = args.get(1).map(String::as_str) {
+        settings.snitch = true;
+    }
 
     // setup mpsc channel for sending messages to the log writer
     let (sender_handle, receiver_handle) = mpsc::channel::<LogMsg>(1024);
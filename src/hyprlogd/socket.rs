@@ -3,14 +3,14 @@ use std::{fs::remove_file, path::Path};
 use tokio::sync::mpsc::Sender;
 
 use crate::log_writer::{LogMsg, log_error};
+use crate::sockets::COMMAND_SOCKET;
 
 use tokio::net::UnixDatagram;
 pub async fn start_socket_listener(sender: Sender<LogMsg>) -> std::io::Result<()> {
-    const SOCKET_PATH: &str = "/tmp/hyprlog.sock";
-    if Path::new(SOCKET_PATH).exists() {
-        let _ = remove_file(SOCKET_PATH);
+    if Path::new(COMMAND_SOCKET).exists() {
+        let _ = remove_file(COMMAND_SOCKET);
     }
-    let sock = UnixDatagram::bind(SOCKET_PATH)?; // no per-connection tasks
+    let sock = UnixDatagram::bind(COMMAND_SOCKET)?; // no per-connection tasks
 
     let mut buf = vec![0u8; 256];
     loop {
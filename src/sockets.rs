@@ -0,0 +1,6 @@
+// shared by hyprlog and hyprlogd, so both binaries always agree on where to find each other
+
+/// hyprlogd listens here for commands like idle and resume
+pub const COMMAND_SOCKET: &str = "/tmp/hyprlog.sock";
+/// hyprlogd sends every row it writes here when it runs as `hyprlogd snitch`
+pub const SNITCH_SOCKET: &str = "/tmp/hyprlog-snitch.sock";